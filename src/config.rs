@@ -0,0 +1,77 @@
+use crate::metrics::MetricGroup;
+use crate::CollectArgs;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Default values for `erldash run` options loaded from a TOML file.
+///
+/// Options explicitly given on the command line take precedence over the values in this file.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    pub polling_interval: Option<NonZeroUsize>,
+    pub cookie: Option<String>,
+    pub port: Option<u16>,
+    pub include: Option<Vec<MetricGroup>>,
+    pub exclude: Option<Vec<MetricGroup>>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Returns `$HOME/.config/erldash/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|dir| dir.join(".config").join("erldash").join("config.toml"))
+    }
+
+    /// Loads the config file specified by `args` (or the default one if it exists).
+    pub fn load(args: &CollectArgs) -> anyhow::Result<Self> {
+        if args.no_config {
+            return Ok(Self::default());
+        }
+        if let Some(path) = &args.config {
+            return Self::load_from_file(path);
+        }
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load_from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file: {}", path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .with_context(|| format!("failed to parse config file: {}", path.display()))?;
+        for key in config.unknown.keys() {
+            eprintln!(
+                "Warning: unknown key {key:?} in config file {}",
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    /// Fills the options that are not specified on the command line.
+    pub fn apply(self, args: &mut CollectArgs) {
+        if args.polling_interval.is_none() {
+            args.polling_interval = self.polling_interval;
+        }
+        // The cookie of a config file is only the fallback of the other cookie sources.
+        if args.cookie.is_none() && args.cookie_file.is_none() && args.vm_args.is_none() {
+            args.cookie = self.cookie;
+        }
+        if args.port.is_none() {
+            args.port = self.port;
+        }
+        if args.include.is_empty() {
+            args.include = self.include.unwrap_or_default();
+        }
+        if args.exclude.is_empty() {
+            args.exclude = self.exclude.unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! User-defined panel layouts of the dashboard (`--dashboard`).
+use anyhow::Context;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// Layout of the dashboard body loaded from a TOML file.
+///
+/// ```toml
+/// columns = 2
+///
+/// [[panels]]
+/// title = "Memory"
+/// metrics = ["memory.total_bytes", "memory.binary_bytes", "memory.ets_bytes"]
+/// chart = "memory.total_bytes"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dashboard {
+    /// Number of panels per row (default: 2).
+    #[serde(default = "default_columns")]
+    pub columns: NonZeroUsize,
+
+    pub panels: Vec<Panel>,
+}
+
+impl Dashboard {
+    /// Loads the layout file, or returns `Ok(None)` if it doesn't exist so that the default layout is used.
+    pub fn load_if_exists(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            log::warn!(
+                "dashboard file {} doesn't exist; using the default layout",
+                path.display()
+            );
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read dashboard file: {}", path.display()))?;
+        let dashboard: Self = toml::from_str(&text)
+            .with_context(|| format!("failed to parse dashboard file: {}", path.display()))?;
+        anyhow::ensure!(
+            !dashboard.panels.is_empty(),
+            "dashboard file {} defines no panels",
+            path.display()
+        );
+        Ok(Some(dashboard))
+    }
+
+    /// Returns the panels split into rows of `columns` panels.
+    pub fn rows(&self) -> impl Iterator<Item = &[Panel]> {
+        self.panels.chunks(self.columns.get())
+    }
+}
+
+fn default_columns() -> NonZeroUsize {
+    NonZeroUsize::new(2).expect("non-zero")
+}
+
+/// Named group of metrics shown as a table (and optionally a chart) in the dashboard.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Panel {
+    pub title: String,
+
+    /// Metric names shown in the panel in this order.
+    ///
+    /// A name ending with `*` (e.g., `utilization.scheduler.*`) matches all the metrics with that prefix.
+    pub metrics: Vec<String>,
+
+    /// Metric charted under the table of the panel.
+    #[serde(default)]
+    pub chart: Option<String>,
+}
+
+impl Panel {
+    /// Returns the names in `available` (sorted) that match the metrics of the panel, in the panel order.
+    pub fn select<'a>(&self, available: impl Iterator<Item = &'a str> + Clone) -> Vec<&'a str> {
+        let mut names = Vec::new();
+        for pattern in &self.metrics {
+            let matched = available
+                .clone()
+                .filter(|name| match pattern.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == pattern,
+                });
+            for name in matched {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+}
@@ -0,0 +1,137 @@
+use crate::erlang::RpcClient;
+use crate::metrics::{self, MetricValue, Metrics};
+use crate::{CollectArgs, DumpArgs};
+
+/// Output format of the `dump` command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DumpFormat {
+    /// Print the value of each metric on its own line.
+    #[default]
+    Value,
+
+    /// Print a JSON object mapping metric names to values.
+    Json,
+}
+
+pub fn run(args: DumpArgs) -> anyhow::Result<()> {
+    let metrics = metrics::collect_once::<RpcClient>(args.collect)?;
+    for line in format_values(&metrics, &args.metric, args.format)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Returns the output lines for the given metrics.
+///
+/// With [`DumpFormat::Value`], the values are printed in the order of `--metric` (duplicates included)
+/// so that scripts can pick a value by its line number.
+fn format_values(
+    metrics: &Metrics,
+    names: &[String],
+    format: DumpFormat,
+) -> anyhow::Result<Vec<String>> {
+    let values = names
+        .iter()
+        .map(|name| {
+            let Some(value) = metrics.items.get(name) else {
+                let names = metrics
+                    .items
+                    .keys()
+                    .map(|name| format!("- {name}"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                anyhow::bail!("unknown metric {name:?}. Available metrics are:\n{names}");
+            };
+            Ok((name, json_value(value)))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(match format {
+        DumpFormat::Value => values.iter().map(|(_, value)| value.to_string()).collect(),
+        DumpFormat::Json => {
+            let map = values
+                .into_iter()
+                .map(|(name, value)| (name.clone(), value))
+                .collect::<serde_json::Map<_, _>>();
+            vec![serde_json::Value::Object(map).to_string()]
+        }
+    })
+}
+
+/// Prints the names and kinds of all the metrics collected from the target node, grouped by namespace.
+pub fn list_metrics(args: CollectArgs) -> anyhow::Result<()> {
+    let metrics = metrics::collect_once::<RpcClient>(args)?;
+
+    let mut current_namespace = None;
+    for (name, value) in &metrics.items {
+        let namespace = name.split('.').next().unwrap_or(name);
+        if current_namespace != Some(namespace) {
+            if current_namespace.is_some() {
+                println!();
+            }
+            println!("{namespace}:");
+            current_namespace = Some(namespace);
+        }
+        println!("  {name} ({})", kind(value));
+    }
+    Ok(())
+}
+
+fn kind(value: &MetricValue) -> &'static str {
+    match value {
+        MetricValue::Gauge { .. } => "gauge",
+        MetricValue::Counter { .. } => "counter",
+        MetricValue::Utilization { .. } => "utilization",
+        MetricValue::FloatGauge { .. } => "gauge",
+    }
+}
+
+fn json_value(value: &MetricValue) -> serde_json::Value {
+    match value {
+        MetricValue::Gauge { value, .. } => (*value).into(),
+        MetricValue::Counter { raw_value, .. } => (*raw_value).into(),
+        MetricValue::Utilization { value, .. } => (*value).into(),
+        MetricValue::FloatGauge { value, .. } => (*value).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_values_follows_metric_order() {
+        let gauge = |value| MetricValue::Gauge {
+            value,
+            parent: None,
+        };
+        let metrics = Metrics {
+            timestamp: Default::default(),
+            items: [
+                ("memory.total_bytes", gauge(1000)),
+                ("statistics.run_queue", gauge(3)),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect(),
+        };
+        let names = [
+            "statistics.run_queue",
+            "memory.total_bytes",
+            "statistics.run_queue",
+        ]
+        .map(str::to_owned);
+
+        let lines = format_values(&metrics, &names, DumpFormat::Value).unwrap();
+        assert_eq!(lines, ["3", "1000", "3"]);
+
+        let lines = format_values(&metrics, &names, DumpFormat::Json).unwrap();
+        assert_eq!(
+            lines,
+            [r#"{"memory.total_bytes":1000,"statistics.run_queue":3}"#]
+        );
+
+        let names = ["foo".to_owned()];
+        assert!(format_values(&metrics, &names, DumpFormat::Value).is_err());
+    }
+}
@@ -1,7 +1,109 @@
+use anyhow::Context;
 use erl_dist::node::NodeName;
-use erl_dist::term::{Atom, List, Map, Term, Tuple};
+use erl_dist::term::{Atom, BigInteger, FixInteger, List, Map, Term, Tuple};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `system_info/1` items shown in the node info panel, along with their labels.
+pub const NODE_INFO_ITEMS: &[(&str, &str)] = &[
+    ("OTP release", "otp_release"),
+    ("ERTS version", "version"),
+    ("Architecture", "system_architecture"),
+    ("Word size (bytes)", "wordsize"),
+    ("Build type", "build_type"),
+    ("SMP support", "smp_support"),
+    ("Dirty CPU schedulers", "dirty_cpu_schedulers"),
+    ("Dirty IO schedulers", "dirty_io_schedulers"),
+];
+
+/// Errors returned by [`Rpc`] and [`MetricsSource`].
+#[derive(Debug, thiserror::Error)]
+pub enum ErlangError {
+    /// The node (or its EPMD) could not be reached.
+    #[error("failed to connect to {target}{hint}")]
+    ConnectionFailed {
+        target: String,
+        hint: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The node closed the connection in the middle of the handshake.
+    ///
+    /// Erlang nodes don't tell why they reject a handshake, so this shows the likely causes instead.
+    #[error(
+        "{node} closed the connection during the distribution handshake. Possible causes are:\n\
+         - the cookie is wrong (see `--cookie`, `--cookie-file` or `$HOME/.erlang.cookie`)\n\
+         - port {port} is not the distribution port of the node (e.g., a wrong `--port`)\n\
+         - the node uses TLS distribution (`-proto_dist inet_tls`), which is not supported\n\
+         - {}",
+        name_type.mismatch_hint()
+    )]
+    HandshakeRejected {
+        node: String,
+        port: u16,
+        name_type: NameType,
+        #[source]
+        source: erl_rpc::ConnectError,
+    },
+
+    /// The distribution handshake with the node failed (e.g., due to a wrong cookie or a name type mismatch).
+    #[error(
+        "failed to establish a distribution connection with {node}. Check the cookie. Also, {}",
+        name_type.mismatch_hint()
+    )]
+    HandshakeFailed {
+        node: String,
+        name_type: NameType,
+        #[source]
+        source: erl_rpc::ConnectError,
+    },
+
+    #[error(transparent)]
+    Epmd(#[from] erl_dist::epmd::EpmdError),
+
+    #[error("{node} is not registered in EPMD on {host}")]
+    NodeNotFound { node: String, host: String },
+
+    #[error("{node} only supports too old distribution protocol versions")]
+    TooOldProtocolVersion { node: String },
+
+    #[error(
+        "Unix-domain socket transport ({}) is not supported. Use `--port` to connect over TCP without EPMD",
+        path.display()
+    )]
+    UnsupportedTransport { path: PathBuf },
+
+    /// A term returned by the node doesn't have the expected shape.
+    #[error("expected {expected}, but got {got}")]
+    UnexpectedTerm { expected: String, got: String },
+
+    /// An item expected in a batched result or a msacc map is missing.
+    #[error("no such {kind} item: {name}")]
+    MissingItem { kind: &'static str, name: String },
+
+    #[error(transparent)]
+    Rpc(#[from] erl_rpc::CallError),
+}
+
+impl ErlangError {
+    fn unexpected_term(expected: impl Into<String>, got: impl std::fmt::Display) -> Self {
+        Self::UnexpectedTerm {
+            expected: expected.into(),
+            got: got.to_string(),
+        }
+    }
+
+    fn missing_item(kind: &'static str, name: &str) -> Self {
+        Self::MissingItem {
+            kind,
+            name: name.to_owned(),
+        }
+    }
+}
+
+pub type ErlangResult<T> = Result<T, ErlangError>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemVersion(String);
@@ -10,89 +112,466 @@ impl SystemVersion {
     pub fn get(&self) -> &str {
         &self.0
     }
+
+    /// Returns the major OTP release parsed from a string like `"Erlang/OTP 26 [erts-14.0] ..."`.
+    pub fn otp_release(&self) -> Option<u32> {
+        let rest = self.0.split_once("OTP ")?.1;
+        let digits = rest
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .filter(|s| !s.is_empty())?;
+        digits.parse().ok()
+    }
 }
 
 pub fn find_cookie() -> anyhow::Result<String> {
-    if let Some(dir) = dirs::home_dir().filter(|dir| dir.join(".erlang.cookie").exists()) {
-        let cookie = std::fs::read_to_string(dir.join(".erlang.cookie"))?;
-        Ok(cookie)
+    if let Some(path) = cookie_file_candidates()
+        .into_iter()
+        .find(|path| path.exists())
+    {
+        let cookie = std::fs::read_to_string(path)?;
+        Ok(strip_newline(&cookie).to_owned())
     } else {
         anyhow::bail!("Could not find the cookie file $HOME/.erlang.cookie. Please specify `-cookie` arg instead.");
     }
 }
 
+/// Removes the line terminator that editors often append to cookie files.
+///
+/// Only `\n` and `\r` are stripped since the other characters (including spaces and symbols)
+/// are a part of the cookie and are passed to the handshake as is.
+fn strip_newline(cookie: &str) -> &str {
+    cookie.trim_end_matches(['\n', '\r'])
+}
+
+/// Returns the paths where the default `.erlang.cookie` file may be located.
+///
+/// On Windows, Erlang looks for the cookie in `%HOMEDRIVE%%HOMEPATH%`, which can differ from
+/// the profile directory (`%USERPROFILE%`) returned by `dirs::home_dir()`, so both are checked.
+fn cookie_file_candidates() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        if let (Some(mut drive), Some(path)) =
+            (std::env::var_os("HOMEDRIVE"), std::env::var_os("HOMEPATH"))
+        {
+            drive.push(path);
+            dirs.push(PathBuf::from(drive));
+        }
+    }
+    dirs.extend(dirs::home_dir());
+    dirs.into_iter()
+        .map(|dir| dir.join(".erlang.cookie"))
+        .collect()
+}
+
+/// Finds the cookie for `erlang_node` in a file containing `node@host=cookie` lines (or a single bare cookie).
+///
+/// The file is read as `node@host=cookie` lines only if the left side of `=` in a line is a node name,
+/// so a bare cookie may contain any symbols, including `=` and a leading `#`.
+pub fn find_cookie_in_file(path: &Path, erlang_node: &NodeName) -> anyhow::Result<String> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read cookie file: {}", path.display()))?;
+
+    // Surrounding whitespace is ignored, so cookies with leading or trailing spaces must be quoted.
+    fn trim(s: &str) -> String {
+        let s = s.trim();
+        s.strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(s)
+            .to_owned()
+    }
+    fn split_entry(line: &str) -> Option<(&str, &str)> {
+        line.split_once('=')
+            .filter(|(node, _)| trim(node).parse::<NodeName>().is_ok())
+    }
+    let is_comment = |line: &str| line.trim_start().starts_with('#');
+    let lines = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    if !lines
+        .iter()
+        .any(|line| !is_comment(line) && split_entry(line).is_some())
+    {
+        let [cookie] = lines.as_slice() else {
+            anyhow::bail!(
+                "invalid cookie file {}: expected a single cookie or `node@host=cookie` lines",
+                path.display()
+            );
+        };
+        // A bare cookie is used verbatim (`lines()` has already removed the line terminator).
+        return Ok(cookie.to_string());
+    }
+
+    let mut found = None;
+    for line in lines.into_iter().filter(|line| !is_comment(line)) {
+        let Some((node, cookie)) = split_entry(line) else {
+            anyhow::bail!(
+                "invalid line in cookie file {}: expected `node@host=cookie`, but got {line:?}",
+                path.display()
+            );
+        };
+        if found.is_none() && trim(node) == erlang_node.to_string() {
+            found = Some(trim(cookie));
+        }
+    }
+    found.ok_or_else(|| {
+        anyhow::anyhow!(
+            "no cookie for {erlang_node} is found in the cookie file {}",
+            path.display()
+        )
+    })
+}
+
+/// Node name and cookie given in a `vm.args` file of a release.
+#[derive(Debug, Default, Clone)]
+pub struct VmArgs {
+    pub name: Option<NodeName>,
+    pub cookie: Option<String>,
+}
+
+impl VmArgs {
+    /// Loads the `-name` and `-setcookie` flags from a `vm.args` file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read vm.args file: {}", path.display()))?;
+        let mut vm_args = Self::default();
+        for line in content.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let Some((flag, value)) = line.trim().split_once(char::is_whitespace) else {
+                continue;
+            };
+            // The value is the rest of the line so that a quoted cookie may contain spaces.
+            let value = value.trim();
+            let value = ['"', '\'']
+                .into_iter()
+                .find_map(|q| value.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(value);
+            match flag {
+                "-name" => {
+                    let name = value.parse().with_context(|| {
+                        format!(
+                            "invalid `-name` in vm.args file {}: {value:?}",
+                            path.display()
+                        )
+                    })?;
+                    vm_args.name = Some(name);
+                }
+                "-setcookie" => {
+                    vm_args.cookie = Some(value.to_owned());
+                }
+                _ => {}
+            }
+        }
+        Ok(vm_args)
+    }
+}
+
+/// Finds the only Erlang node registered in the local EPMD.
+pub async fn find_local_node() -> anyhow::Result<NodeName> {
+    let host = "localhost";
+    let connection = smol::net::TcpStream::connect((host, erl_dist::epmd::DEFAULT_EPMD_PORT))
+        .await
+        .with_context(|| format!("failed to connect to EPMD on {host}"))?;
+    let names = erl_dist::epmd::EpmdClient::new(connection)
+        .get_names()
+        .await?;
+    match names.as_slice() {
+        [] => anyhow::bail!("no Erlang nodes are registered in EPMD on {host}"),
+        [(name, _)] => Ok(NodeName::new(name, host)?),
+        _ => {
+            let names = names
+                .iter()
+                .map(|(name, port)| format!("- {name}@{host} (port={port})"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            anyhow::bail!(
+                "multiple Erlang nodes are registered in EPMD on {host}. Please specify one of them:\n{names}"
+            );
+        }
+    }
+}
+
+/// Returns the entries of all the Erlang nodes registered in the EPMD on `host`.
+pub async fn list_epmd_nodes(host: &str) -> anyhow::Result<Vec<erl_dist::epmd::NodeEntry>> {
+    // As `EpmdClient` is consumed by each request, a new connection is made for each of them.
+    let connect = || async {
+        smol::net::TcpStream::connect((host, erl_dist::epmd::DEFAULT_EPMD_PORT))
+            .await
+            .with_context(|| format!("failed to connect to EPMD on {host}"))
+    };
+    let names = erl_dist::epmd::EpmdClient::new(connect().await?)
+        .get_names()
+        .await?;
+    let mut entries = Vec::with_capacity(names.len());
+    for (name, _) in names {
+        // A node may be unregistered after `get_names()`.
+        if let Some(entry) = erl_dist::epmd::EpmdClient::new(connect().await?)
+            .get_node(&name)
+            .await?
+        {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Erlang expression that sums the message queue lengths of all processes on the node.
+///
+/// The sum is computed on the node so that only a single integer is transferred,
+/// but the cost is still proportional to the number of processes.
+pub const TOTAL_MESSAGE_QUEUE_LEN_EXPR: &str = "lists:foldl(fun(P, Acc) -> \
+     case erlang:process_info(P, message_queue_len) of \
+         {message_queue_len, N} -> Acc + N; \
+         undefined -> Acc \
+     end \
+ end, 0, erlang:processes()).";
+
+/// Looks up the distribution port of `erlang_node` in the EPMD listening on `epmd_addr`.
+pub async fn find_node_port(epmd_addr: (&str, u16), erlang_node: &NodeName) -> ErlangResult<u16> {
+    let connection = smol::net::TcpStream::connect(epmd_addr)
+        .await
+        .map_err(|source| ErlangError::ConnectionFailed {
+            target: format!("EPMD on {}", erlang_node.host()),
+            hint: if source.kind() == std::io::ErrorKind::ConnectionRefused {
+                ". EPMD is not running on the host. If the node runs without EPMD, specify its port with `--port`"
+            } else {
+                ""
+            },
+            source,
+        })?;
+    let Some(entry) = erl_dist::epmd::EpmdClient::new(connection)
+        .get_node(erlang_node.name())
+        .await?
+    else {
+        return Err(ErlangError::NodeNotFound {
+            node: erlang_node.name().to_owned(),
+            host: erlang_node.host().to_owned(),
+        });
+    };
+    if entry.highest_version < 6 {
+        return Err(ErlangError::TooOldProtocolVersion {
+            node: erlang_node.to_string(),
+        });
+    }
+    Ok(entry.port)
+}
+
+/// Address of an established distribution connection.
 #[derive(Debug, Clone)]
-pub struct RpcClient {
-    handle: erl_rpc::RpcClientHandle,
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+    pub kind: EndpointKind,
 }
 
-impl RpcClient {
-    pub async fn connect(
-        erlang_node: &NodeName,
-        port: Option<u16>,
-        cookie: &str,
-    ) -> anyhow::Result<Self> {
-        let client = if let Some(port) = port {
-            erl_rpc::RpcClient::connect_with_port(&erlang_node.to_string(), port, cookie).await?
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{} ", self.host, self.port)?;
+        match &self.kind {
+            EndpointKind::Epmd => write!(f, "(port resolved via EPMD)"),
+            EndpointKind::Direct => write!(f, "(direct connection without EPMD)"),
+            EndpointKind::Ssh(destination) => write!(f, "(forwarded via SSH to {destination})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointKind {
+    /// The port was looked up in EPMD.
+    Epmd,
+
+    /// The port was given by `--port`.
+    Direct,
+
+    /// The connection goes through `--ssh` port forwarding to the given destination.
+    Ssh(String),
+}
+
+/// Transport used for the Erlang distribution connection.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Transport {
+    /// TCP connection to the host of the node name.
+    #[default]
+    Tcp,
+
+    /// Unix-domain socket (not supported yet).
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for Transport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "tcp" {
+            Ok(Self::Tcp)
+        } else if let Some(path) = s.strip_prefix("unix:") {
+            Ok(Self::Unix(PathBuf::from(path)))
         } else {
-            erl_rpc::RpcClient::connect(&erlang_node.to_string(), cookie).await?
-        };
-        let handle = client.handle();
-        smol::spawn(async {
-            if let Err(e) = client.run().await {
-                log::error!("Erlang RPC Client error: {e}");
+            anyhow::bail!("expected `tcp` or `unix:PATH`, but got {s:?}")
+        }
+    }
+}
+
+/// Whether a node name is a short name (`-sname`) or a long name (`-name`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NameType {
+    #[default]
+    Short,
+    Long,
+}
+
+impl NameType {
+    /// Detects the name type from the host part of `node` (a fully qualified host or an IP address is long).
+    pub fn detect(node: &NodeName) -> Self {
+        if node.host().contains('.') {
+            Self::Long
+        } else {
+            Self::Short
+        }
+    }
+
+    fn mismatch_hint(self) -> &'static str {
+        match self {
+            Self::Short => {
+                "the node name is a short name, so the node must be started with `-sname`. \
+                 If it was started with `-name`, specify its fully qualified name (e.g., `foo@host.example.com`)"
             }
-        })
-        .detach();
+            Self::Long => {
+                "the node name is a long name, so the node must be started with `-name`. \
+                 If it was started with `-sname`, specify its short name (e.g., `foo@host`)"
+            }
+        }
+    }
+}
 
-        Ok(Self { handle })
+impl std::fmt::Display for NameType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Short => write!(f, "short"),
+            Self::Long => write!(f, "long"),
+        }
     }
+}
+
+/// Returns `true` if the peer closed the connection or refused to continue during the handshake.
+fn is_handshake_rejection(e: &erl_rpc::ConnectError) -> bool {
+    use erl_dist::handshake::{HandshakeError, HandshakeStatus};
+    use std::io::ErrorKind;
+
+    match e {
+        erl_rpc::ConnectError::HandshakeError(HandshakeError::Io(e)) => matches!(
+            e.kind(),
+            ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset | ErrorKind::BrokenPipe
+        ),
+        erl_rpc::ConnectError::HandshakeError(HandshakeError::NotAllowed)
+        | erl_rpc::ConnectError::UnexpectedHandshakeStatus {
+            status: HandshakeStatus::NotAllowed,
+        } => true,
+        _ => false,
+    }
+}
+
+/// Value of an evaluated expression (see [`Rpc::eval_number`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+/// RPC interface to an Erlang node.
+///
+/// The metric getters are provided on top of [`Rpc::call`], so an implementation only needs to
+/// connect to a node and forward the calls to it. [`RpcClient`] is the one used for real nodes.
+// The futures are only driven by `smol::block_on()` on the calling thread, so they need not be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait Rpc: 'static + Sized + Clone + Send + std::fmt::Debug {
+    /// Connects to the given node.
+    ///
+    /// `name_type` is the name type of the node given by the user.
+    async fn connect(
+        erlang_node: &NodeName,
+        name_type: NameType,
+        port: Option<u16>,
+        transport: &Transport,
+        cookie: &str,
+    ) -> ErlangResult<Self>;
+
+    /// Returns the port the connection was established to.
+    fn port(&self) -> u16;
+
+    /// Calls `module:function(args...)` on the node.
+    async fn call(&self, module: &str, function: &str, args: List) -> ErlangResult<Term>;
 
-    pub async fn get_system_version(&self) -> anyhow::Result<SystemVersion> {
+    async fn get_system_version(&self) -> ErlangResult<SystemVersion> {
         let term = self
-            .handle
-            .clone()
             .call(
-                "erlang".into(),
-                "system_info".into(),
+                "erlang",
+                "system_info",
                 List::from(vec![Atom::from("system_version").into()]),
             )
             .await?;
-        term_to_string(term).map(SystemVersion)
+        term_to_string_lossy(term).map(SystemVersion)
     }
 
-    pub async fn get_system_info_u64(&self, item_name: &str) -> anyhow::Result<u64> {
+    /// Returns the static facts of [`NODE_INFO_ITEMS`] as `(label, value)` pairs.
+    ///
+    /// Items that are not supported by the node are skipped.
+    async fn get_node_info(&self) -> Vec<(String, String)> {
+        let mut info = Vec::new();
+        for (label, item_name) in NODE_INFO_ITEMS {
+            let result = self
+                .call(
+                    "erlang",
+                    "system_info",
+                    List::from(vec![Atom::from(*item_name).into()]),
+                )
+                .await;
+            match result {
+                Ok(term) => info.push((label.to_string(), term_to_display_string(term))),
+                Err(e) => log::warn!("failed to get system_info({item_name}): {e}"),
+            }
+        }
+        info
+    }
+
+    async fn get_system_info_u64(&self, item_name: &str) -> ErlangResult<u64> {
         let term = self
-            .handle
-            .clone()
             .call(
-                "erlang".into(),
-                "system_info".into(),
+                "erlang",
+                "system_info",
                 List::from(vec![Atom::from(item_name).into()]),
             )
             .await?;
         term_to_u64(term)
     }
 
-    pub async fn get_statistics_1st_u64(&self, item_name: &str) -> anyhow::Result<u64> {
+    async fn get_statistics_u64(&self, item_name: &str) -> ErlangResult<u64> {
+        let term = self.get_statistics(item_name).await?;
+        term_to_u64(term)
+    }
+
+    async fn get_statistics_1st_u64(&self, item_name: &str) -> ErlangResult<u64> {
         let term = self.get_statistics(item_name).await?;
         term_to_tuple_1st_u64(term)
     }
 
-    pub async fn get_statistics_u64_list(&self, item_name: &str) -> anyhow::Result<Vec<u64>> {
+    async fn get_statistics_u64_list(&self, item_name: &str) -> ErlangResult<Vec<u64>> {
         let term = self.get_statistics(item_name).await?;
         term_to_u64_list(term)
     }
 
-    pub async fn get_statistics_io(&self) -> anyhow::Result<(u64, u64)> {
+    async fn get_statistics_u64_tuple(&self, item_name: &str) -> ErlangResult<Vec<u64>> {
+        let term = self.get_statistics(item_name).await?;
+        term_to_u64_tuple(term)
+    }
+
+    async fn get_statistics_io(&self) -> ErlangResult<(u64, u64)> {
         let term = self.get_statistics("io").await?;
-        let tuple = term_to_tuple(term)?;
-        let in_bytes = term_to_tuple_2nd_u64(tuple.elements[0].clone())?;
-        let out_bytes = term_to_tuple_2nd_u64(tuple.elements[1].clone())?;
-        Ok((in_bytes, out_bytes))
+        term_to_io(term)
     }
 
-    pub async fn get_statistics_microstate_accounting(&self) -> anyhow::Result<Vec<MSAccThread>> {
+    async fn get_statistics_microstate_accounting(&self) -> ErlangResult<Vec<MSAccThread>> {
         let term = self.get_statistics("microstate_accounting").await?;
         term_to_list(term)?
             .elements
@@ -101,49 +580,97 @@ impl RpcClient {
             .collect()
     }
 
-    pub async fn set_system_flag_bool(&self, name: &str, value: &str) -> anyhow::Result<bool> {
+    async fn set_system_flag_bool(&self, name: &str, value: &str) -> ErlangResult<bool> {
         let term = self
-            .handle
-            .clone()
             .call(
-                "erlang".into(),
-                "system_flag".into(),
+                "erlang",
+                "system_flag",
                 List::from(vec![Atom::from(name).into(), Atom::from(value).into()]),
             )
             .await?;
         term_to_bool(term)
     }
 
-    pub async fn get_memory(&self) -> anyhow::Result<BTreeMap<String, u64>> {
+    async fn get_memory(&self) -> ErlangResult<BTreeMap<String, u64>> {
+        let term = self.call("erlang", "memory", List::nil()).await?;
+        term_to_memory(term)
+    }
+
+    /// Parses an expression collecting `items` on the node so that it can be evaluated by
+    /// [`Rpc::get_all_metrics_batched`].
+    async fn prepare_batched_call(&self, items: &BatchedItems) -> ErlangResult<BatchedCall> {
+        let exprs = self.parse_exprs(&items.to_expr()).await?;
+        Ok(BatchedCall { exprs })
+    }
+
+    /// Collects the values of the items given to [`Rpc::prepare_batched_call`] by a single RPC.
+    async fn get_all_metrics_batched(&self, call: &BatchedCall) -> ErlangResult<BatchedValues> {
+        let value = self.eval_exprs(&call.exprs).await?;
+        BatchedValues::from_term(value)
+    }
+
+    /// Parses Erlang expressions (terminated by `.`) on the node via `erl_scan` and `erl_parse`.
+    async fn parse_exprs(&self, source: &str) -> ErlangResult<ParsedExprs> {
+        let source = source
+            .chars()
+            .map(|c| FixInteger::from(c as i32).into())
+            .collect::<Vec<Term>>();
         let term = self
-            .handle
-            .clone()
-            .call("erlang".into(), "memory".into(), List::nil())
+            .call(
+                "erl_scan",
+                "string",
+                List::from(vec![List::from(source).into()]),
+            )
             .await?;
-        term_to_list(term)?
-            .elements
-            .into_iter()
-            .map(|x| {
-                let tuple = term_to_tuple(x)?;
-                anyhow::ensure!(
-                    tuple.elements.len() == 2,
-                    "expected a two-elements tuple, but got {}",
-                    tuple
-                );
-                let key = term_to_atom(tuple.elements[0].clone())?;
-                let value = term_to_u64(tuple.elements[1].clone())?;
-                Ok((key.name, value))
-            })
-            .collect()
+        let tokens = term_to_ok_value(term)?;
+
+        let term = self
+            .call("erl_parse", "parse_exprs", List::from(vec![tokens]))
+            .await?;
+        let exprs = term_to_ok_value(term)?;
+        Ok(ParsedExprs { exprs })
     }
 
-    async fn get_statistics(&self, item_name: &str) -> anyhow::Result<Term> {
+    /// Evaluates the parsed expressions on the node via `erl_eval` and returns the value of the last one.
+    async fn eval_exprs(&self, exprs: &ParsedExprs) -> ErlangResult<Term> {
         let term = self
-            .handle
-            .clone()
             .call(
-                "erlang".into(),
-                "statistics".into(),
+                "erl_eval",
+                "exprs",
+                List::from(vec![exprs.exprs.clone(), List::nil().into()]),
+            )
+            .await?;
+        let tuple = term_to_tuple(term)?;
+        if tuple.elements.len() != 3 {
+            return Err(ErlangError::unexpected_term(
+                "`{value, Value, Bindings}`",
+                tuple,
+            ));
+        }
+        Ok(tuple.elements[1].clone())
+    }
+
+    /// Evaluates the parsed expressions on the node and returns the integer or float value of the last one.
+    async fn eval_number(&self, exprs: &ParsedExprs) -> ErlangResult<Number> {
+        match self.eval_exprs(exprs).await? {
+            Term::Float(v) => Ok(Number::Float(v.value)),
+            term @ (Term::FixInteger(_) | Term::BigInteger(_)) => {
+                term_to_i64(term).map(Number::Integer)
+            }
+            term => Err(ErlangError::unexpected_term("an integer or a float", term)),
+        }
+    }
+
+    /// Evaluates the parsed expressions on the node and returns the integer value of the last one.
+    async fn eval_i64(&self, exprs: &ParsedExprs) -> ErlangResult<i64> {
+        term_to_i64(self.eval_exprs(exprs).await?)
+    }
+
+    async fn get_statistics(&self, item_name: &str) -> ErlangResult<Term> {
+        let term = self
+            .call(
+                "erlang",
+                "statistics",
                 List::from(vec![Atom::from(item_name).into()]),
             )
             .await?;
@@ -151,53 +678,379 @@ impl RpcClient {
     }
 }
 
-fn term_to_tuple_1st_u64(term: Term) -> anyhow::Result<u64> {
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    handle: erl_rpc::RpcClientHandle,
+    port: u16,
+}
+
+impl Rpc for RpcClient {
+    /// Connects to the given node.
+    ///
+    /// The connection is established as a hidden node since `erl_rpc` doesn't set
+    /// the `PUBLISHED` distribution flag during the handshake.
+    /// The name of the local node is assigned by the peer (i.e., a dynamic node name requested via
+    /// the `NAME_ME` flag), so multiple clients connecting to the same node never collide.
+    ///
+    /// `name_type` is the name type of the node given by the user. It is only used to hint
+    /// at a `-name`/`-sname` mismatch when the handshake fails since `erl_rpc` always
+    /// introduces the local node with a short name.
+    async fn connect(
+        erlang_node: &NodeName,
+        name_type: NameType,
+        port: Option<u16>,
+        transport: &Transport,
+        cookie: &str,
+    ) -> ErlangResult<Self> {
+        log::debug!("connecting to {erlang_node} as a {name_type} name");
+        if let Transport::Unix(path) = transport {
+            // `erl_rpc` only supports TCP connections.
+            return Err(ErlangError::UnsupportedTransport { path: path.clone() });
+        }
+        // The port is resolved here instead of by `erl_rpc` so that it can be shown to users.
+        let port = match port {
+            Some(port) => port,
+            None => {
+                find_node_port(
+                    (erlang_node.host(), erl_dist::epmd::DEFAULT_EPMD_PORT),
+                    erlang_node,
+                )
+                .await?
+            }
+        };
+        let client = erl_rpc::RpcClient::connect_with_port(&erlang_node.to_string(), port, cookie)
+            .await
+            .map_err(|e| match e {
+                erl_rpc::ConnectError::IoError(source) => ErlangError::ConnectionFailed {
+                    target: format!("{erlang_node} (port {port})"),
+                    hint: if source.kind() == std::io::ErrorKind::ConnectionRefused {
+                        ". Nothing is listening on the port. Check that the node is running"
+                    } else {
+                        ""
+                    },
+                    source,
+                },
+                source if is_handshake_rejection(&source) => ErlangError::HandshakeRejected {
+                    node: erlang_node.to_string(),
+                    port,
+                    name_type,
+                    source,
+                },
+                source => ErlangError::HandshakeFailed {
+                    node: erlang_node.to_string(),
+                    name_type,
+                    source,
+                },
+            })?;
+        let handle = client.handle();
+        smol::spawn(async {
+            if let Err(e) = client.run().await {
+                log::error!("Erlang RPC Client error: {e}");
+            }
+        })
+        .detach();
+
+        Ok(Self { handle, port })
+    }
+
+    /// Returns the port the connection was established to.
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    async fn call(&self, module: &str, function: &str, args: List) -> ErlangResult<Term> {
+        let term = self
+            .handle
+            .clone()
+            .call(module.into(), function.into(), args)
+            .await?;
+        Ok(term)
+    }
+}
+
+/// `erlang:statistics/1`, `erlang:system_info/1` and `erlang:memory/0` items collected by a batched RPC.
+///
+/// Microstate accounting is not included as it is measured separately (see `--msacc-window`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BatchedItems {
+    pub statistics: Vec<&'static str>,
+    pub system_info: Vec<&'static str>,
+    pub memory: bool,
+}
+
+impl BatchedItems {
+    pub fn is_empty(&self) -> bool {
+        self.statistics.is_empty() && self.system_info.is_empty() && !self.memory
+    }
+
+    /// Returns an Erlang expression that evaluates to
+    /// `{[{StatisticsItem, Value}], [{SystemInfoItem, Value}], Memory}`.
+    ///
+    /// `Memory` is `[]` if [`BatchedItems::memory`] is `false`.
+    fn to_expr(&self) -> String {
+        format!(
+            "{{[{{I, erlang:statistics(I)}} || I <- [{}]], [{{I, erlang:system_info(I)}} || I <- [{}]], {}}}.",
+            self.statistics.join(", "),
+            self.system_info.join(", "),
+            if self.memory { "erlang:memory()" } else { "[]" }
+        )
+    }
+}
+
+/// Erlang expressions parsed on the target node by [`Rpc::parse_exprs`].
+#[derive(Debug, Clone)]
+pub struct ParsedExprs {
+    exprs: Term,
+}
+
+/// [`BatchedItems`] expression parsed on the target node.
+#[derive(Debug, Clone)]
+pub struct BatchedCall {
+    exprs: ParsedExprs,
+}
+
+/// Values collected by [`Rpc::get_all_metrics_batched`].
+#[derive(Debug, Clone)]
+pub struct BatchedValues {
+    statistics: BTreeMap<String, Term>,
+    system_info: BTreeMap<String, Term>,
+    memory: Term,
+}
+
+impl BatchedValues {
+    fn from_term(term: Term) -> ErlangResult<Self> {
+        let tuple = term_to_tuple(term)?;
+        if tuple.elements.len() != 3 {
+            return Err(ErlangError::unexpected_term(
+                "a three-elements tuple",
+                tuple,
+            ));
+        }
+        let mut elements = tuple.elements.into_iter();
+        let mut next_items = || -> ErlangResult<BTreeMap<String, Term>> {
+            term_to_list(elements.next().expect("unreachable"))?
+                .elements
+                .into_iter()
+                .map(term_to_key_value)
+                .collect()
+        };
+        let statistics = next_items()?;
+        let system_info = next_items()?;
+        let memory = elements.next().expect("unreachable");
+        Ok(Self {
+            statistics,
+            system_info,
+            memory,
+        })
+    }
+
+    fn get_statistics(&self, item_name: &str) -> ErlangResult<Term> {
+        self.statistics
+            .get(item_name)
+            .cloned()
+            .ok_or_else(|| ErlangError::missing_item("statistics", item_name))
+    }
+}
+
+/// Source of metric values: either individual RPCs or values collected by a batched RPC.
+#[derive(Debug)]
+pub enum MetricsSource<'a, C> {
+    Rpc(&'a C),
+    Batched(BatchedValues),
+}
+
+impl<C: Rpc> MetricsSource<'_, C> {
+    pub async fn get_system_info_u64(&self, item_name: &str) -> ErlangResult<u64> {
+        match self {
+            Self::Rpc(client) => client.get_system_info_u64(item_name).await,
+            Self::Batched(values) => {
+                let term = values
+                    .system_info
+                    .get(item_name)
+                    .cloned()
+                    .ok_or_else(|| ErlangError::missing_item("system_info", item_name))?;
+                term_to_u64(term)
+            }
+        }
+    }
+
+    pub async fn get_statistics_u64(&self, item_name: &str) -> ErlangResult<u64> {
+        match self {
+            Self::Rpc(client) => client.get_statistics_u64(item_name).await,
+            Self::Batched(values) => term_to_u64(values.get_statistics(item_name)?),
+        }
+    }
+
+    pub async fn get_statistics_1st_u64(&self, item_name: &str) -> ErlangResult<u64> {
+        match self {
+            Self::Rpc(client) => client.get_statistics_1st_u64(item_name).await,
+            Self::Batched(values) => term_to_tuple_1st_u64(values.get_statistics(item_name)?),
+        }
+    }
+
+    pub async fn get_statistics_u64_list(&self, item_name: &str) -> ErlangResult<Vec<u64>> {
+        match self {
+            Self::Rpc(client) => client.get_statistics_u64_list(item_name).await,
+            Self::Batched(values) => term_to_u64_list(values.get_statistics(item_name)?),
+        }
+    }
+
+    pub async fn get_statistics_u64_tuple(&self, item_name: &str) -> ErlangResult<Vec<u64>> {
+        match self {
+            Self::Rpc(client) => client.get_statistics_u64_tuple(item_name).await,
+            Self::Batched(values) => term_to_u64_tuple(values.get_statistics(item_name)?),
+        }
+    }
+
+    pub async fn get_statistics_io(&self) -> ErlangResult<(u64, u64)> {
+        match self {
+            Self::Rpc(client) => client.get_statistics_io().await,
+            Self::Batched(values) => term_to_io(values.get_statistics("io")?),
+        }
+    }
+
+    pub async fn get_memory(&self) -> ErlangResult<BTreeMap<String, u64>> {
+        match self {
+            Self::Rpc(client) => client.get_memory().await,
+            Self::Batched(values) => term_to_memory(values.memory.clone()),
+        }
+    }
+}
+
+/// Converts `{{input, Input}, {output, Output}}` returned by `erlang:statistics(io)`.
+fn term_to_io(term: Term) -> ErlangResult<(u64, u64)> {
     let tuple = term_to_tuple(term)?;
-    anyhow::ensure!(
-        !tuple.elements.is_empty(),
-        "expected a non empty tuple, but got {}",
-        tuple
-    );
-    term_to_u64(tuple.elements[0].clone())
+    let [input, output] = <[Term; 2]>::try_from(tuple.elements).map_err(|elements| {
+        ErlangError::unexpected_term("`{{input, _}, {output, _}}`", Tuple::from(elements))
+    })?;
+    let in_bytes = term_to_io_bytes(input, "input")?;
+    let out_bytes = term_to_io_bytes(output, "output")?;
+    Ok((in_bytes, out_bytes))
 }
 
-fn term_to_tuple_2nd_u64(term: Term) -> anyhow::Result<u64> {
+fn term_to_io_bytes(term: Term, expected_key: &str) -> ErlangResult<u64> {
+    let expected = || format!("`{{{expected_key}, Bytes}}` element of `statistics(io)`");
+    let (key, value) = term_to_key_value(term.clone())
+        .map_err(|_| ErlangError::unexpected_term(expected(), &term))?;
+    if key != expected_key {
+        return Err(ErlangError::unexpected_term(expected(), term));
+    }
+    term_to_u64(value)
+}
+
+fn term_to_memory(term: Term) -> ErlangResult<BTreeMap<String, u64>> {
+    term_to_list(term)?
+        .elements
+        .into_iter()
+        .map(|x| {
+            let (key, value) = term_to_key_value(x)?;
+            Ok((key, term_to_u64(value)?))
+        })
+        .collect()
+}
+
+fn term_to_key_value(term: Term) -> ErlangResult<(String, Term)> {
     let tuple = term_to_tuple(term)?;
-    anyhow::ensure!(
-        tuple.elements.len() >= 2,
-        "expected a tuple having 2 or more elements, but got {}",
-        tuple
-    );
-    term_to_u64(tuple.elements[1].clone())
+    if tuple.elements.len() != 2 {
+        return Err(ErlangError::unexpected_term("a two-elements tuple", tuple));
+    }
+    let mut elements = tuple.elements.into_iter();
+    let key = term_to_atom(elements.next().expect("unreachable"))?;
+    Ok((key.name, elements.next().expect("unreachable")))
 }
 
-fn term_to_u64(term: Term) -> anyhow::Result<u64> {
+/// Extracts `Value` from `{ok, Value}` or `{ok, Value, _}`.
+fn term_to_ok_value(term: Term) -> ErlangResult<Term> {
+    let tuple = term_to_tuple(term)?;
+    match tuple.elements.first() {
+        Some(Term::Atom(atom)) if atom.name == "ok" && tuple.elements.len() >= 2 => {
+            Ok(tuple.elements[1].clone())
+        }
+        _ => Err(ErlangError::unexpected_term("`{ok, _}`", tuple)),
+    }
+}
+
+fn term_to_tuple_1st_u64(term: Term) -> ErlangResult<u64> {
+    let tuple = term_to_tuple(term)?;
+    if tuple.elements.is_empty() {
+        return Err(ErlangError::unexpected_term("a non empty tuple", tuple));
+    }
+    term_to_u64(tuple.elements[0].clone())
+}
+
+/// Converts a tuple of integers such as `{Number_of_GCs, Words_Reclaimed, 0}` to `Vec<u64>`.
+fn term_to_u64_tuple(term: Term) -> ErlangResult<Vec<u64>> {
+    term_to_tuple(term)?
+        .elements
+        .into_iter()
+        .map(term_to_u64)
+        .collect()
+}
+
+fn term_to_i64(term: Term) -> ErlangResult<i64> {
+    match term {
+        Term::FixInteger(v) => Ok(v.value.into()),
+        Term::BigInteger(v) => i64::try_from(&v.value)
+            .map_err(|_| ErlangError::unexpected_term("an integer in the range of i64", v.value)),
+        v => Err(ErlangError::unexpected_term("an integer", v)),
+    }
+}
+
+/// Converts an integer term to `u64`.
+///
+/// Integers larger than `u64::MAX` are saturated so that a single oversized value does not fail the whole poll.
+fn term_to_u64(term: Term) -> ErlangResult<u64> {
     let v = match term {
-        Term::FixInteger(v) => v.value.try_into()?,
-        Term::BigInteger(v) => v.value.try_into()?,
-        v => anyhow::bail!("{} is not an integer", v),
+        Term::FixInteger(v) => v
+            .value
+            .try_into()
+            .map_err(|_| ErlangError::unexpected_term("a non-negative integer", v.value))?,
+        Term::BigInteger(v) if v.value < BigInteger::from(0).value => {
+            return Err(ErlangError::unexpected_term(
+                "a non-negative integer",
+                v.value,
+            ));
+        }
+        Term::BigInteger(v) => u64::try_from(&v.value).unwrap_or_else(|_| {
+            log::warn!("{} exceeds u64::MAX and is saturated", v.value);
+            u64::MAX
+        }),
+        v => return Err(ErlangError::unexpected_term("an integer", v)),
     };
     Ok(v)
 }
 
-fn term_to_string(term: Term) -> anyhow::Result<String> {
+/// Formats a term for display, showing strings and atoms without quotes.
+fn term_to_display_string(term: Term) -> String {
+    match term {
+        Term::Atom(atom) => atom.name,
+        term @ Term::List(_) => {
+            term_to_string_lossy(term.clone()).unwrap_or_else(|_| term.to_string())
+        }
+        term => term.to_string(),
+    }
+}
+
+/// Converts a byte list into a string for display, replacing invalid UTF-8 sequences with `U+FFFD`.
+fn term_to_string_lossy(term: Term) -> ErlangResult<String> {
     let bytes = term_to_list(term)?
         .elements
         .into_iter()
         .map(term_to_u8)
-        .collect::<anyhow::Result<Vec<_>>>()?;
-    Ok(String::from_utf8(bytes)?)
+        .collect::<ErlangResult<Vec<_>>>()?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
 }
 
-fn term_to_u8(term: Term) -> anyhow::Result<u8> {
-    if let Term::FixInteger(v) = term {
-        Ok(u8::try_from(v.value)?)
-    } else {
-        anyhow::bail!("expected an integer, but got {}", term)
+fn term_to_u8(term: Term) -> ErlangResult<u8> {
+    match term {
+        Term::FixInteger(v) => {
+            u8::try_from(v.value).map_err(|_| ErlangError::unexpected_term("a byte", v.value))
+        }
+        term => Err(ErlangError::unexpected_term("a byte", term)),
     }
 }
 
-fn term_to_u64_list(term: Term) -> anyhow::Result<Vec<u64>> {
+fn term_to_u64_list(term: Term) -> ErlangResult<Vec<u64>> {
     term_to_list(term)?
         .elements
         .into_iter()
@@ -205,28 +1058,28 @@ fn term_to_u64_list(term: Term) -> anyhow::Result<Vec<u64>> {
         .collect()
 }
 
-fn term_to_bool(term: Term) -> anyhow::Result<bool> {
+fn term_to_bool(term: Term) -> ErlangResult<bool> {
     let atom = term_to_atom(term)?;
     match atom.name.as_str() {
         "true" => Ok(true),
         "false" => Ok(false),
-        _ => anyhow::bail!("expected 'true' or 'false', but got {}", atom.name),
+        _ => Err(ErlangError::unexpected_term("'true' or 'false'", atom.name)),
     }
 }
 
-fn term_to_atom(term: Term) -> anyhow::Result<Atom> {
+fn term_to_atom(term: Term) -> ErlangResult<Atom> {
     term.try_into()
-        .map_err(|x| anyhow::anyhow!("expected an atom, but got {x}"))
+        .map_err(|x| ErlangError::unexpected_term("an atom", x))
 }
 
-fn term_to_tuple(term: Term) -> anyhow::Result<Tuple> {
+fn term_to_tuple(term: Term) -> ErlangResult<Tuple> {
     term.try_into()
-        .map_err(|x| anyhow::anyhow!("expected a tuple, but got {x}"))
+        .map_err(|x| ErlangError::unexpected_term("a tuple", x))
 }
 
-fn term_to_list(term: Term) -> anyhow::Result<List> {
+fn term_to_list(term: Term) -> ErlangResult<List> {
     term.try_into()
-        .map_err(|x| anyhow::anyhow!("expected a list, but got {x}"))
+        .map_err(|x| ErlangError::unexpected_term("a list", x))
 }
 
 #[derive(Debug, Clone)]
@@ -237,10 +1090,10 @@ pub struct MSAccThread {
 }
 
 impl MSAccThread {
-    fn from_term(term: Term) -> anyhow::Result<Self> {
+    fn from_term(term: Term) -> ErlangResult<Self> {
         let map: Map = term
             .try_into()
-            .map_err(|x| anyhow::anyhow!("expected a map, but got {x}"))?;
+            .map_err(|x| ErlangError::unexpected_term("a map", x))?;
         let mut thread_id = None;
         let mut thread_type = None;
         let mut counters = BTreeMap::new();
@@ -255,7 +1108,7 @@ impl MSAccThread {
                 "counters" => {
                     let counters_map: Map = v
                         .try_into()
-                        .map_err(|x| anyhow::anyhow!("expected a map, but got {x}"))?;
+                        .map_err(|x| ErlangError::unexpected_term("a map", x))?;
                     for (k, v) in counters_map.entries {
                         counters.insert(term_to_atom(k)?.name, term_to_u64(v)?);
                     }
@@ -266,9 +1119,210 @@ impl MSAccThread {
             }
         }
         Ok(Self {
-            thread_id: thread_id.ok_or_else(|| anyhow::anyhow!("missing 'id' key"))?,
-            thread_type: thread_type.ok_or_else(|| anyhow::anyhow!("missing 'type' key"))?,
+            thread_id: thread_id.ok_or_else(|| ErlangError::missing_item("msacc", "id"))?,
+            thread_type: thread_type.ok_or_else(|| ErlangError::missing_item("msacc", "type"))?,
             counters,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_type_detect_works() {
+        for (node, expected) in [
+            ("foo@localhost", NameType::Short),
+            ("foo@host.example.com", NameType::Long),
+            ("foo@127.0.0.1", NameType::Long),
+        ] {
+            assert_eq!(NameType::detect(&node.parse().unwrap()), expected, "{node}");
+        }
+    }
+
+    #[test]
+    fn batched_items_to_expr_works() {
+        let items = BatchedItems {
+            statistics: vec!["io", "runtime"],
+            system_info: vec!["process_count"],
+            memory: true,
+        };
+        assert_eq!(
+            items.to_expr(),
+            "{[{I, erlang:statistics(I)} || I <- [io, runtime]], \
+             [{I, erlang:system_info(I)} || I <- [process_count]], erlang:memory()}."
+        );
+
+        let items = BatchedItems {
+            statistics: vec!["io"],
+            ..Default::default()
+        };
+        assert!(!items.is_empty());
+        assert_eq!(
+            items.to_expr(),
+            "{[{I, erlang:statistics(I)} || I <- [io]], [{I, erlang:system_info(I)} || I <- []], []}."
+        );
+        assert!(BatchedItems::default().is_empty());
+    }
+
+    #[test]
+    fn term_to_u64_works() {
+        assert_eq!(term_to_u64(FixInteger::from(0).into()).unwrap(), 0);
+        assert_eq!(term_to_u64(FixInteger::from(123).into()).unwrap(), 123);
+        assert!(term_to_u64(FixInteger::from(-1).into()).is_err());
+
+        let max = BigInteger::from(u64::MAX);
+        assert_eq!(term_to_u64(max.clone().into()).unwrap(), u64::MAX);
+        assert_eq!(
+            term_to_u64(BigInteger::from(1u64 << 40).into()).unwrap(),
+            1 << 40
+        );
+
+        // Integers larger than `u64::MAX` are saturated.
+        let too_large = BigInteger {
+            value: max.value * 2u32,
+        };
+        assert_eq!(term_to_u64(too_large.into()).unwrap(), u64::MAX);
+
+        assert!(term_to_u64(BigInteger::from(i64::MIN).into()).is_err());
+        assert!(term_to_u64(Atom::from("foo").into()).is_err());
+    }
+
+    fn key_value(key: &str, value: Term) -> Term {
+        Tuple::from(vec![Atom::from(key).into(), value]).into()
+    }
+
+    #[test]
+    fn term_to_io_works() {
+        let io = Tuple::from(vec![
+            key_value("input", FixInteger::from(10).into()),
+            key_value("output", BigInteger::from(u64::MAX).into()),
+        ]);
+        assert_eq!(term_to_io(io.into()).unwrap(), (10, u64::MAX));
+
+        let malformed: Vec<Term> = vec![
+            // Not a tuple.
+            List::nil().into(),
+            // Too few or too many elements.
+            Tuple::from(vec![key_value("input", FixInteger::from(1).into())]).into(),
+            Tuple::from(vec![
+                key_value("input", FixInteger::from(1).into()),
+                key_value("output", FixInteger::from(2).into()),
+                key_value("output", FixInteger::from(3).into()),
+            ])
+            .into(),
+            // Swapped keys.
+            Tuple::from(vec![
+                key_value("output", FixInteger::from(1).into()),
+                key_value("input", FixInteger::from(2).into()),
+            ])
+            .into(),
+            // Elements that are not `{Key, Value}` tuples.
+            Tuple::from(vec![
+                FixInteger::from(1).into(),
+                key_value("output", FixInteger::from(2).into()),
+            ])
+            .into(),
+            Tuple::from(vec![
+                key_value("input", FixInteger::from(1).into()),
+                Tuple::from(vec![Atom::from("output").into()]).into(),
+            ])
+            .into(),
+            // Non-integer values.
+            Tuple::from(vec![
+                key_value("input", Atom::from("undefined").into()),
+                key_value("output", FixInteger::from(2).into()),
+            ])
+            .into(),
+        ];
+        for term in malformed {
+            let display = term.to_string();
+            assert!(
+                matches!(term_to_io(term), Err(ErlangError::UnexpectedTerm { .. })),
+                "{display}"
+            );
+        }
+    }
+
+    fn byte_list(bytes: &[i32]) -> Term {
+        List::from(
+            bytes
+                .iter()
+                .map(|b| FixInteger::from(*b).into())
+                .collect::<Vec<Term>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn term_to_string_lossy_works() {
+        assert_eq!(
+            term_to_string_lossy(byte_list(b"OTP 26".map(i32::from).as_slice())).unwrap(),
+            "OTP 26"
+        );
+        assert_eq!(
+            term_to_string_lossy(byte_list(&[0xe2, 0x9c, 0x93])).unwrap(),
+            "\u{2713}"
+        );
+
+        // Invalid UTF-8 sequences are replaced with U+FFFD.
+        assert_eq!(
+            term_to_string_lossy(byte_list(&[0x61, 0xff, 0x62, 0xe2, 0x9c])).unwrap(),
+            "a\u{fffd}b\u{fffd}"
+        );
+
+        // Elements that are not bytes are errors.
+        assert!(term_to_string_lossy(byte_list(&[0x61, 256])).is_err());
+        assert!(term_to_string_lossy(byte_list(&[-1])).is_err());
+        assert!(term_to_string_lossy(Atom::from("foo").into()).is_err());
+    }
+
+    #[test]
+    fn strip_newline_works() {
+        assert_eq!(strip_newline("secret\n"), "secret");
+        assert_eq!(strip_newline("secret\r\n"), "secret");
+        assert_eq!(strip_newline("secret"), "secret");
+        assert_eq!(strip_newline(" secret \n"), " secret ");
+        assert_eq!(strip_newline("a=b#c\"d!$\r\n"), "a=b#c\"d!$");
+    }
+
+    #[test]
+    fn find_cookie_in_file_works() {
+        let node: NodeName = "foo@localhost".parse().unwrap();
+        let path = std::env::temp_dir().join(format!("erldash-cookie-test-{}", std::process::id()));
+        let find = |content: &str| {
+            std::fs::write(&path, content).unwrap();
+            find_cookie_in_file(&path, &node)
+        };
+
+        // Bare cookies.
+        assert_eq!(find("secret\n").unwrap(), "secret");
+        assert_eq!(find("secret\r\n").unwrap(), "secret");
+        assert_eq!(find("secret").unwrap(), "secret");
+
+        // `node@host=cookie` lines.
+        let content = "bar@localhost=other\nfoo@localhost=secret";
+        assert_eq!(find(content).unwrap(), "secret");
+        assert_eq!(find(&format!("{content}\n")).unwrap(), "secret");
+        assert_eq!(find(&content.replace('\n', "\r\n")).unwrap(), "secret");
+        assert_eq!(
+            find("# comment\r\nfoo@localhost = \" secret \"\r\n").unwrap(),
+            " secret "
+        );
+        assert!(find("bar@localhost=other\n").is_err());
+        assert!(find("foo@localhost=secret\nsecret\n").is_err());
+
+        // Bare cookies with symbols are not mistaken for `node@host=cookie` lines or comments.
+        assert_eq!(find("a=b#c\"d!$\n").unwrap(), "a=b#c\"d!$");
+        assert_eq!(find("#a=b#c\"d!$\r\n").unwrap(), "#a=b#c\"d!$");
+        assert_eq!(find(" a=b \n").unwrap(), " a=b ");
+        assert!(find("one\ntwo\n").is_err());
+        assert_eq!(
+            find("# foo@localhost=old\nfoo@localhost=a=b#c\"d!$\n").unwrap(),
+            "a=b#c\"d!$"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+use crate::metrics::{Header, Marker, MetricValue, Metrics, MetricsPoller, RECORD_FORMAT_VERSION};
+use anyhow::Context;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Format of the timestamps in exported data (CSV files, snapshots, WebSocket messages and copied series).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    /// RFC 3339 (ISO 8601) with milliseconds, e.g., `2024-01-01T09:00:00.000+09:00`.
+    #[default]
+    Iso,
+
+    /// Seconds since the Unix epoch with a millisecond fraction, e.g., `1704067200.000`.
+    Epoch,
+
+    /// Milliseconds since the Unix epoch, e.g., `1704067200000`.
+    #[value(name = "epoch_ms", alias = "epoch-ms")]
+    EpochMs,
+}
+
+static TIMESTAMP_FORMAT: OnceLock<TimestampFormat> = OnceLock::new();
+
+/// Sets the format used by `format_timestamp()` and `timestamp_json()`.
+///
+/// This can be called only once, and subsequent calls are ignored.
+pub fn set_timestamp_format(format: TimestampFormat) {
+    let _ = TIMESTAMP_FORMAT.set(format);
+}
+
+/// Formats a wall-clock time (see [`Header::wall_clock`]) in the format given by `--timestamp-format`.
+pub fn format_timestamp(time: chrono::DateTime<chrono::Local>) -> String {
+    match TIMESTAMP_FORMAT.get().copied().unwrap_or_default() {
+        TimestampFormat::Iso => time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        TimestampFormat::Epoch => format!("{:.3}", time.timestamp_millis() as f64 / 1000.0),
+        TimestampFormat::EpochMs => time.timestamp_millis().to_string(),
+    }
+}
+
+/// Like `format_timestamp()`, but epoch timestamps are given as JSON numbers instead of strings.
+pub fn timestamp_json(time: chrono::DateTime<chrono::Local>) -> serde_json::Value {
+    match TIMESTAMP_FORMAT.get().copied().unwrap_or_default() {
+        TimestampFormat::Iso => format_timestamp(time).into(),
+        TimestampFormat::Epoch => (time.timestamp_millis() as f64 / 1000.0).into(),
+        TimestampFormat::EpochMs => time.timestamp_millis().into(),
+    }
+}
+
+/// How counter metrics are written to a CSV file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CsvCounters {
+    /// The raw (monotonically increasing) counter values.
+    Raw,
+
+    /// The delta per second.
+    #[default]
+    Rate,
+}
+
+pub fn write_csv(poller: &MetricsPoller, path: &Path, counters: CsvCounters) -> anyhow::Result<()> {
+    let metrics_log = poller
+        .get_metrics_range(0, Duration::ZERO, poller.replay_last_time())?
+        .collect::<Vec<_>>();
+    let names = metrics_log
+        .iter()
+        .flat_map(|metrics| metrics.items.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create CSV file: {}", path.display()))?;
+    writer.write_record(std::iter::once("timestamp").chain(names.iter().map(|x| x.as_str())))?;
+
+    let header = poller.header();
+    for metrics in metrics_log {
+        let timestamp = format_timestamp(header.wall_clock(metrics.timestamp));
+        let values = names.iter().map(|name| {
+            metrics
+                .items
+                .get(*name)
+                .and_then(|value| csv_value(value, counters))
+                .unwrap_or_default()
+        });
+        writer.write_record(std::iter::once(timestamp).chain(values))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn csv_value(value: &MetricValue, counters: CsvCounters) -> Option<String> {
+    match value {
+        MetricValue::Gauge { value, .. } => Some(value.to_string()),
+        MetricValue::Counter { raw_value, .. } if counters == CsvCounters::Raw => {
+            Some(raw_value.to_string())
+        }
+        MetricValue::Counter { value, .. } => value.map(|v| v.to_string()),
+        MetricValue::Utilization { value, .. } => Some(value.to_string()),
+        MetricValue::FloatGauge { value, .. } => Some(value.to_string()),
+    }
+}
+
+/// Writes the metrics and markers of the replayed record file within `range` to a new record file
+/// (`replay --extract`).
+///
+/// The timestamps are shifted so that the new file starts at the beginning of the range.
+pub fn write_extract(
+    poller: &MetricsPoller,
+    range: Range<Duration>,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let last_time = poller.replay_last_time();
+    anyhow::ensure!(
+        range.end <= last_time,
+        "the range {:?}..{:?} exceeds the recording, which ends at {:?}",
+        range.start,
+        range.end,
+        last_time
+    );
+    let metrics_log = poller
+        .get_metrics_range(0, range.start, range.end)?
+        .collect::<Vec<_>>();
+    anyhow::ensure!(
+        !metrics_log.is_empty(),
+        "no metrics are recorded in the range {:?}..{:?}",
+        range.start,
+        range.end
+    );
+
+    let header = poller.header();
+    let header = Header {
+        format_version: RECORD_FORMAT_VERSION,
+        start_time: header.start_time + range.start,
+        ..header.clone()
+    };
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("failed to create record file: {}", path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    write_json_line(&mut writer, &header)?;
+
+    let mut markers = poller
+        .markers()
+        .iter()
+        .filter(|m| range.contains(&m.timestamp))
+        .peekable();
+    for metrics in metrics_log {
+        while let Some(marker) = markers.next_if(|m| m.timestamp <= metrics.timestamp) {
+            write_json_line(
+                &mut writer,
+                &Marker {
+                    timestamp: marker.timestamp - range.start,
+                    marker: marker.marker.clone(),
+                },
+            )?;
+        }
+        write_json_line(
+            &mut writer,
+            &Metrics {
+                timestamp: metrics.timestamp - range.start,
+                items: metrics.items.clone(),
+            },
+        )?;
+    }
+    for marker in markers {
+        write_json_line(
+            &mut writer,
+            &Marker {
+                timestamp: marker.timestamp - range.start,
+                marker: marker.marker.clone(),
+            },
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_json_line(writer: &mut impl Write, value: &impl serde::Serialize) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Prints the metadata of a replayed record file as a JSON object.
+///
+/// As the polling interval is not recorded, it is estimated as the median interval between polls.
+pub fn print_info(poller: &MetricsPoller) -> anyhow::Result<()> {
+    let metrics_log = poller
+        .get_metrics_range(0, Duration::ZERO, poller.replay_last_time())?
+        .collect::<Vec<_>>();
+    let names = metrics_log
+        .iter()
+        .flat_map(|metrics| metrics.items.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut intervals = metrics_log
+        .windows(2)
+        .map(|w| (w[1].timestamp.saturating_sub(w[0].timestamp)).as_secs_f64())
+        .collect::<Vec<_>>();
+    intervals.sort_by(|a, b| a.total_cmp(b));
+    let polling_interval = intervals.get(intervals.len() / 2);
+
+    let duration = match (metrics_log.first(), metrics_log.last()) {
+        (Some(first), Some(last)) => (last.timestamp - first.timestamp).as_secs_f64(),
+        _ => 0.0,
+    };
+
+    let header = poller.header();
+    let info = serde_json::json!({
+        "format_version": header.format_version,
+        "node_name": header.node_name,
+        "system_version": header.system_version.get(),
+        "node_info": header
+            .node_info
+            .iter()
+            .map(|(label, value)| (label.clone(), serde_json::Value::from(value.as_str())))
+            .collect::<serde_json::Map<_, _>>(),
+        "start_time": header.start_time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "polling_interval_secs": polling_interval,
+        "duration_secs": duration,
+        "polls": metrics_log.len(),
+        "markers": poller.markers().len(),
+        "metrics": names,
+    });
+    println!("{}", serde_json::to_string_pretty(&info)?);
+    Ok(())
+}
+
+/// Writes the last metrics of each node to `path` as JSON (`--snapshot-on-exit`).
+///
+/// Nodes from which no metrics have been collected are written with `"metrics": null` and a `"note"`.
+pub fn write_snapshot(path: &Path, nodes: &[(&Header, Option<&Metrics>)]) -> anyhow::Result<()> {
+    let nodes = nodes
+        .iter()
+        .map(|(header, metrics)| {
+            let mut node = serde_json::json!({
+                "node_name": header.node_name,
+                "system_version": header.system_version.get(),
+                "timestamp": metrics.map(|metrics| timestamp_json(header.wall_clock(metrics.timestamp))),
+                "metrics": metrics.map(|metrics| &metrics.items),
+            });
+            if metrics.is_none() {
+                node["note"] = "no metrics were collected before exit".into();
+            }
+            node
+        })
+        .collect::<Vec<_>>();
+    let snapshot = serde_json::json!({
+        "written_at": timestamp_json(chrono::Local::now()),
+        "nodes": nodes,
+    });
+    std::fs::write(path, serde_json::to_string_pretty(&snapshot)?)
+        .with_context(|| format!("failed to write snapshot file: {}", path.display()))?;
+    Ok(())
+}
@@ -1,8 +1,17 @@
 //! A simple, terminal-based Erlang dashboard.
+use anyhow::Context;
 use std::path::PathBuf;
+use std::time::Duration;
+pub mod config;
+pub mod dashboard;
+pub mod dump;
 pub mod erlang;
+pub mod export;
 pub mod metrics;
+pub mod nodes;
+pub mod ssh;
 pub mod ui;
+pub mod ws;
 
 #[derive(Debug, Clone, clap::Subcommand)]
 pub enum Command {
@@ -11,38 +20,226 @@ pub enum Command {
 
     /// Replay a previously recorded dashboard session.
     Replay(ReplayArgs),
+
+    /// Collect metrics once and print the specified ones to stdout.
+    Dump(DumpArgs),
+
+    /// List the Erlang nodes registered in EPMD on a host.
+    Nodes(NodesArgs),
+}
+
+impl Command {
+    /// Returns `None` if the command doesn't show the dashboard.
+    pub fn ui_args(&self) -> Option<&UiArgs> {
+        match self {
+            Self::Run(args) => Some(&args.ui),
+            Self::Replay(args) => Some(&args.ui),
+            Self::Dump(_) => None,
+            Self::Nodes(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, clap::Args)]
 pub struct RunArgs {
-    /// Target Erlang node name.
-    pub erlang_node: erl_dist::node::NodeName,
+    #[clap(flatten)]
+    pub collect: CollectArgs,
+
+    /// If specified, the collected metrics will be recorded to the given file and can be replayed later.
+    #[clap(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// If specified, the last collected metrics of each node are written to the given JSON file when erldash exits.
+    ///
+    /// Unlike `--record`, only the final state is written.
+    #[clap(long, value_name = "FILE")]
+    pub snapshot_on_exit: Option<PathBuf>,
+
+    /// Broadcast each poll as a JSON message to WebSocket clients connecting to the given address
+    /// (e.g., `127.0.0.1:9090`).
+    #[clap(long, value_name = "ADDR")]
+    pub ws_listen: Option<std::net::SocketAddr>,
+
+    /// If specified, metrics are collected for this duration (e.g., `30s`, `10m`) and then erldash exits.
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub duration: Option<Duration>,
 
-    /// Erlang metrics polling interval (in seconds).
-    #[clap(long, short = 'i', default_value = "1")]
-    pub polling_interval: std::num::NonZeroUsize,
+    /// Print the names of all the collectable metrics and exit.
+    #[clap(long)]
+    pub list_metrics: bool,
+
+    #[clap(flatten)]
+    pub ui: UiArgs,
+}
+
+/// Options for connecting to the target nodes and selecting the collected metrics, shared by `run` and `dump`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct CollectArgs {
+    /// Target Erlang node names.
+    ///
+    /// If multiple nodes are specified, the 'n' key switches the node shown in the dashboard.
+    /// If omitted, the `-name` flag in the `--vm-args` file is used.
+    /// A name may end with `:PORT` (e.g., `foo@127.0.0.1:9000`), which is equivalent to `--port PORT`.
+    #[clap(
+        required_unless_present_any = ["auto_node", "vm_args"],
+        conflicts_with = "auto_node"
+    )]
+    pub erlang_nodes: Vec<erl_dist::node::NodeName>,
+
+    /// Connect to the Erlang node registered in the local EPMD instead of specifying its name.
+    ///
+    /// This fails if there are no or multiple registered nodes.
+    #[clap(long)]
+    pub auto_node: bool,
+
+    /// Erlang metrics polling interval (in seconds) [default: 1].
+    #[clap(long, short = 'i')]
+    pub polling_interval: Option<std::num::NonZeroUsize>,
 
     /// Erlang cookie.
     ///
     /// By default, the content of the `$HOME/.erlang.cookie` file is used.
-    #[clap(long, short = 'c')]
+    #[clap(long, short = 'c', conflicts_with = "cookie_file")]
     pub cookie: Option<String>,
 
-    /// If specified, the collected metrics will be recorded to the given file and can be replayed later.
+    /// Path to a file containing per-node cookies.
+    ///
+    /// Each line of the file has the form `node@host=cookie`.
+    /// If the file consists of a single line without `=`, the line is used as the cookie for all nodes.
     #[clap(long, value_name = "FILE")]
-    pub record: Option<PathBuf>,
+    pub cookie_file: Option<PathBuf>,
+
+    /// Path to a `vm.args` file of a release (e.g., generated by rebar3/relx).
+    ///
+    /// The `-setcookie` flag in the file is used as the cookie unless `--cookie` or `--cookie-file` is specified,
+    /// and the `-name` flag is used as the target node name if no node names are given.
+    #[clap(long, value_name = "FILE")]
+    pub vm_args: Option<PathBuf>,
 
     /// Port number on which the target node listens.
     ///
-    /// If specified, `erldash` will connect directly to the node without using EPMD.
+    /// If specified, `erldash` will connect directly to the node without using EPMD
+    /// (e.g., `foo@127.0.0.1 --port 9000` connects to `127.0.0.1:9000`).
     #[clap(long, short)]
     pub port: Option<u16>,
+
+    /// Connect to the target nodes through SSH port forwarding via the given host (e.g., `user@bastion`).
+    ///
+    /// `ssh -N -L LOCAL_PORT:HOST:4369 DESTINATION` is spawned to look up the distribution port in EPMD
+    /// (unless `--port` is specified), and then `ssh -N -L LOCAL_PORT:HOST:PORT DESTINATION` to forward the
+    /// distribution port. HOST is the host part of the node name, which is resolved on the SSH server side.
+    #[clap(long, value_name = "DESTINATION")]
+    pub ssh: Option<String>,
+
+    /// Transport of the distribution connection (`tcp` or `unix:PATH`).
+    ///
+    /// Only `tcp` is currently supported.
+    #[clap(long, default_value = "tcp")]
+    pub transport: erlang::Transport,
+
+    /// If specified, microstate accounting utilization is measured over this duration (e.g., `500ms`, `2s`) at each poll
+    /// instead of over the whole polling interval.
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub msacc_window: Option<Duration>,
+
+    /// Metric groups to collect (all groups are collected by default).
+    #[clap(long, value_enum, value_delimiter = ',')]
+    pub include: Vec<metrics::MetricGroup>,
+
+    /// Metric groups not to collect.
+    #[clap(long, value_enum, value_delimiter = ',')]
+    pub exclude: Vec<metrics::MetricGroup>,
+
+    /// Path to a config file providing default values for the options of this command.
+    ///
+    /// By default, `$HOME/.config/erldash/config.toml` is used if it exists.
+    #[clap(long, value_name = "FILE", conflicts_with = "no_config")]
+    pub config: Option<PathBuf>,
+
+    /// Do not load any config file.
+    #[clap(long)]
+    pub no_config: bool,
+
+    /// Erlang expression evaluated on each poll and shown as the `custom.watch.LABEL` gauge
+    /// (e.g., `--watch 'cache=ets:info(my_cache, size)'`).
+    ///
+    /// The expression must evaluate to an integer or a float. This option can be specified multiple times.
+    #[clap(long, value_name = "LABEL=EXPR")]
+    pub watch: Vec<metrics::WatchExpr>,
+
+    /// Collect `statistics.total_message_queue_len`, the sum of the message queue lengths of all processes,
+    /// at the given interval (e.g., `--mailbox-metric=30s`) [default: 10s].
+    ///
+    /// This metric is expensive as it visits every process on the node, so it is disabled by default
+    /// and sampled less frequently than the other metrics (the last value is repeated in between).
+    #[clap(
+        long,
+        value_name = "INTERVAL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10s",
+        value_parser = parse_duration
+    )]
+    pub mailbox_metric: Option<Duration>,
+
+    /// Collect `erldash.poll_duration_ms`, the time taken by each poll (mostly the RPC round trips to the node).
+    ///
+    /// This shows the overhead of erldash and how slow the node or the network is to respond.
+    #[clap(long)]
+    pub self_metrics: bool,
 }
 
-impl RunArgs {
-    pub fn find_cookie(&self) -> anyhow::Result<String> {
+impl CollectArgs {
+    pub fn polling_interval(&self) -> Duration {
+        let secs = self.polling_interval.map_or(1, |x| x.get());
+        Duration::from_secs(secs as u64)
+    }
+
+    pub fn is_metric_group_enabled(&self, group: metrics::MetricGroup) -> bool {
+        (self.include.is_empty() || self.include.contains(&group)) && !self.exclude.contains(&group)
+    }
+
+    /// Moves the `:PORT` suffixes of the node names (e.g., `foo@127.0.0.1:9000`) to `--port`.
+    ///
+    /// Hosts with multiple colons are IPv6 addresses and are left as they are.
+    pub fn split_node_ports(&mut self) -> anyhow::Result<()> {
+        for erlang_node in &mut self.erlang_nodes {
+            let host = erlang_node.host();
+            let Some((host, port)) = host
+                .split_once(':')
+                .filter(|(host, _)| !host.contains(':'))
+                .filter(|(_, port)| !port.contains(':'))
+            else {
+                continue;
+            };
+            let port = port
+                .parse::<u16>()
+                .with_context(|| format!("invalid port number in node name {erlang_node}"))?;
+            if let Some(other) = self.port.filter(|other| *other != port) {
+                anyhow::bail!(
+                    "the port of node name {erlang_node} conflicts with port {other} \
+                     (given by `--port` or another node name)"
+                );
+            }
+            self.port = Some(port);
+            *erlang_node = erl_dist::node::NodeName::new(erlang_node.name(), host)?;
+        }
+        Ok(())
+    }
+
+    pub fn find_cookie(&self, erlang_node: &erl_dist::node::NodeName) -> anyhow::Result<String> {
         if let Some(cookie) = &self.cookie {
             Ok(cookie.clone())
+        } else if let Some(path) = &self.cookie_file {
+            erlang::find_cookie_in_file(path, erlang_node)
+        } else if let Some(cookie) = self
+            .vm_args
+            .as_ref()
+            .map(|path| erlang::VmArgs::load(path))
+            .transpose()?
+            .and_then(|vm_args| vm_args.cookie)
+        {
+            Ok(cookie)
         } else {
             erlang::find_cookie()
         }
@@ -53,4 +250,232 @@ impl RunArgs {
 pub struct ReplayArgs {
     /// Path to a file containing recorded metrics.
     pub file: PathBuf,
+
+    /// If specified, the recorded metrics are written to the given CSV file instead of being replayed.
+    #[clap(long, value_name = "FILE")]
+    pub to_csv: Option<PathBuf>,
+
+    /// Print the metadata of the record file as JSON and exit.
+    #[clap(long, conflicts_with_all = ["to_csv", "compare"])]
+    pub info: bool,
+
+    /// Another record file to compare with `FILE` side by side (aligned by the offset from the start).
+    #[clap(long, value_name = "FILE", conflicts_with = "to_csv")]
+    pub compare: Option<PathBuf>,
+
+    /// Keep reading the metrics appended to `FILE` while it is being recorded by another erldash instance
+    /// (like `tail -f`).
+    #[clap(long, conflicts_with_all = ["to_csv", "info", "compare"])]
+    pub follow: bool,
+
+    /// Write the metrics (and markers) within the given time range to a new record file given by `--output`,
+    /// instead of replaying them.
+    ///
+    /// The range is given as offsets from the start of the recording such as `00:05:00..00:07:30`
+    /// (`MM:SS` and plain seconds are also accepted).
+    #[clap(
+        long,
+        value_name = "START..END",
+        value_parser = parse_time_range,
+        requires = "output",
+        conflicts_with_all = ["to_csv", "info", "compare", "follow"]
+    )]
+    pub extract: Option<std::ops::Range<Duration>>,
+
+    /// Record file written by `--extract`.
+    #[clap(long, short, value_name = "FILE", requires = "extract")]
+    pub output: Option<PathBuf>,
+
+    /// How counter metrics are written to the CSV file.
+    #[clap(long, value_enum, default_value_t = export::CsvCounters::Rate, requires = "to_csv")]
+    pub csv_counters: export::CsvCounters,
+
+    #[clap(flatten)]
+    pub ui: UiArgs,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct NodesArgs {
+    /// Host whose EPMD is queried.
+    #[clap(long, default_value = "localhost")]
+    pub host: String,
+
+    /// Print the nodes as a JSON array instead of a table.
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct DumpArgs {
+    /// Names of the metrics to print.
+    #[clap(long, required = true)]
+    pub metric: Vec<String>,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = dump::DumpFormat::Value)]
+    pub format: dump::DumpFormat,
+
+    #[clap(flatten)]
+    pub collect: CollectArgs,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct UiArgs {
+    /// Columns shown in the metrics and detail tables.
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "name,value,avg"
+    )]
+    pub columns: Vec<ui::Column>,
+
+    /// Thousands separator style of the displayed numbers.
+    #[clap(long, value_enum, default_value_t = metrics::NumberFormat::Comma)]
+    pub number_format: metrics::NumberFormat,
+
+    /// Number of decimal places of the displayed utilization percentages.
+    #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=6))]
+    pub util_decimals: u8,
+
+    /// Number of decimal places of the displayed floating-point gauges (e.g., `--watch` expressions returning floats).
+    #[clap(long, value_name = "N", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=6))]
+    pub float_decimals: u8,
+
+    /// Color theme of the dashboard.
+    ///
+    /// If the `NO_COLOR` environment variable is set, `mono` is always used.
+    #[clap(long, value_enum, default_value_t = ui::Theme::Dark)]
+    pub theme: ui::Theme,
+
+    /// How long metrics are kept in memory for exports with the 'y' key (e.g., `10m`).
+    ///
+    /// The chart always shows the last 60 seconds, so shorter durations are rejected.
+    #[clap(long, value_name = "DURATION", default_value = "60s", value_parser = parse_retain)]
+    pub retain: Duration,
+
+    /// Start with a compact layout that shows only the metrics table (the 'c' key toggles it).
+    #[clap(long)]
+    pub compact: bool,
+
+    /// Size the table columns to the current values instead of the widest values seen so far.
+    ///
+    /// By default, columns only grow so that the layout doesn't jitter (the 'x' key resets them).
+    #[clap(long)]
+    pub no_sticky_widths: bool,
+
+    /// Show the rates of counter metrics as the moving average of the last N polls.
+    ///
+    /// The chart draws the smoothed rates in bold over the raw ones (the 'r' key toggles the raw ones).
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(2..))]
+    pub smooth: Option<u64>,
+
+    /// Render at most N rows of the metrics and detail tables, followed by a row showing how many are hidden.
+    ///
+    /// Moving the selection scrolls the rows. This keeps rendering fast on nodes with thousands of child metrics.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_rows: Option<u64>,
+
+    /// Start with the metrics whose latest value is zero hidden from the tables (the 'z' key toggles it).
+    #[clap(long)]
+    pub hide_zero: bool,
+
+    /// Hide the sparkline of `utilization.total` over the chart window in the CPU box of the header.
+    #[clap(long)]
+    pub no_header_spark: bool,
+
+    /// Path to a TOML file defining the panels of the dashboard body (see `examples/dashboards/`).
+    ///
+    /// The default layout is used if the file doesn't exist (the 'c' key also switches to it).
+    #[clap(long, value_name = "FILE")]
+    pub dashboard: Option<PathBuf>,
+
+    /// Pause polling while the terminal window is not focused, and resume it when the window is focused again.
+    ///
+    /// This reduces the load on the target nodes when nobody is looking at the dashboard.
+    /// Only works with terminals that report focus changes.
+    #[clap(long)]
+    pub pause_on_blur: bool,
+
+    /// Format of the timestamps in exported data (`--to-csv`, `--snapshot-on-exit`, `--ws-listen`
+    /// and series copied with the 'y' key).
+    #[clap(long, value_enum, default_value_t = export::TimestampFormat::Iso)]
+    pub timestamp_format: export::TimestampFormat,
+
+    /// Maximum number of screen redraws per second.
+    #[clap(long, value_name = "FPS", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub ui_fps: u32,
+}
+
+fn parse_time_range(s: &str) -> anyhow::Result<std::ops::Range<Duration>> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| anyhow::anyhow!("expected `START..END` (e.g., `00:05:00..00:07:30`)"))?;
+    let (start, end) = (parse_time_offset(start)?, parse_time_offset(end)?);
+    anyhow::ensure!(start < end, "the start of the range must be before its end");
+    Ok(start..end)
+}
+
+/// Parses `HH:MM:SS`, `MM:SS` or `SS` (the seconds may have a fractional part).
+fn parse_time_offset(s: &str) -> anyhow::Result<Duration> {
+    let mut secs = 0.0;
+    for (i, part) in s.trim().split(':').enumerate() {
+        anyhow::ensure!(i < 3, "too many `:` in {s:?}");
+        let value = part
+            .parse::<f64>()
+            .with_context(|| format!("invalid time offset: {s:?}"))?;
+        secs = secs * 60.0 + value;
+    }
+    Ok(Duration::try_from_secs_f64(secs)?)
+}
+
+fn parse_retain(s: &str) -> anyhow::Result<Duration> {
+    let duration = parse_duration(s)?;
+    anyhow::ensure!(
+        duration >= Duration::from_secs(ui::CHART_DURATION),
+        "must be at least the chart duration ({}s)",
+        ui::CHART_DURATION
+    );
+    Ok(duration)
+}
+
+fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let duration = if let Some(ms) = s.strip_suffix("ms") {
+        Duration::from_millis(ms.parse()?)
+    } else if let Some(m) = s.strip_suffix('m') {
+        Duration::try_from_secs_f64(m.parse::<f64>()? * 60.0)?
+    } else if let Some(h) = s.strip_suffix('h') {
+        Duration::try_from_secs_f64(h.parse::<f64>()? * 3600.0)?
+    } else {
+        Duration::try_from_secs_f64(s.strip_suffix('s').unwrap_or(s).parse()?)?
+    };
+    anyhow::ensure!(!duration.is_zero(), "duration must be greater than zero");
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_retain_works() {
+        assert_eq!(parse_retain("60s").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_retain("10m").unwrap(), Duration::from_secs(600));
+        assert!(parse_retain("59s").is_err());
+        assert!(parse_retain("500ms").is_err());
+    }
+
+    #[test]
+    fn cookie_option_is_used_verbatim() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            collect: CollectArgs,
+        }
+        let cookie = "a=b#c\"d!$ ";
+        let args =
+            <Cli as clap::Parser>::parse_from(["erldash", "foo@localhost", "--cookie", cookie]);
+        let node = "foo@localhost".parse().unwrap();
+        assert_eq!(args.collect.find_cookie(&node).unwrap(), cookie);
+    }
 }
@@ -1,6 +1,8 @@
 use anyhow::Context;
 use clap::Parser;
-use erldash::{metrics, ui};
+use erldash::{config, dump, export, metrics, nodes, ui};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 /// Erlang Dashboard.
 #[derive(Debug, Parser)]
@@ -20,15 +22,83 @@ struct Args {
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
     setup_logger(&args)?;
 
-    let poller = metrics::MetricsPoller::start_thread(args.command)?;
-    let app = ui::App::new(poller)?;
+    match &mut args.command {
+        erldash::Command::Run(erldash::RunArgs { collect, .. })
+        | erldash::Command::Dump(erldash::DumpArgs { collect, .. }) => {
+            // The ports in the node names are given on the command line, so they take precedence
+            // over the one in the config file.
+            collect.split_node_ports()?;
+            config::Config::load(collect)?.apply(collect);
+        }
+        erldash::Command::Replay(_) | erldash::Command::Nodes(_) => {}
+    }
+    match args.command {
+        erldash::Command::Dump(dump_args) => return dump::run(dump_args),
+        erldash::Command::Nodes(nodes_args) => return nodes::run(nodes_args),
+        erldash::Command::Run(run_args) if run_args.list_metrics => {
+            return dump::list_metrics(run_args.collect);
+        }
+        _ => {}
+    }
+
+    if let Some(ui_args) = args.command.ui_args() {
+        export::set_timestamp_format(ui_args.timestamp_format);
+    }
+    let poller = metrics::MetricsPoller::start_thread(args.command.clone())?;
+    if let erldash::Command::Replay(erldash::ReplayArgs {
+        to_csv: Some(path),
+        csv_counters,
+        ..
+    }) = &args.command
+    {
+        export::write_csv(&poller, path, *csv_counters)?;
+        return Ok(());
+    }
+    if let erldash::Command::Replay(erldash::ReplayArgs {
+        extract: Some(range),
+        output: Some(path),
+        ..
+    }) = &args.command
+    {
+        export::write_extract(&poller, range.clone(), path)?;
+        return Ok(());
+    }
+    if let erldash::Command::Replay(erldash::ReplayArgs { info: true, .. }) = &args.command {
+        export::print_info(&poller)?;
+        return Ok(());
+    }
+
+    let snapshot_on_exit = match &args.command {
+        erldash::Command::Run(run_args) => run_args.snapshot_on_exit.clone(),
+        _ => None,
+    };
+    let ui_args = args
+        .command
+        .ui_args()
+        .cloned()
+        .context("the command doesn't show the dashboard")?;
+    let shutdown = register_shutdown_signals()?;
+    let app = ui::App::new(poller, ui_args, shutdown, snapshot_on_exit)?;
     app.run()?;
     Ok(())
 }
 
+/// Makes termination signals set the returned flag instead of killing the process,
+/// so that the UI can exit in an orderly way and the poller can restore the node's settings.
+///
+/// A second signal terminates the process immediately.
+fn register_shutdown_signals() -> anyhow::Result<Arc<AtomicBool>> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for &signal in signal_hook::consts::TERM_SIGNALS {
+        signal_hook::flag::register_conditional_shutdown(signal, 1, shutdown.clone())?;
+        signal_hook::flag::register(signal, shutdown.clone())?;
+    }
+    Ok(shutdown)
+}
+
 fn setup_logger(args: &Args) -> anyhow::Result<()> {
     if let Some(logfile) = &args.logfile {
         let file = std::fs::OpenOptions::new()
@@ -1,16 +1,70 @@
-use crate::erlang::{MSAccThread, RpcClient, SystemVersion};
-use crate::{Command, ReplayArgs, RunArgs};
+pub mod descriptions;
+
+use crate::erlang::{
+    BatchedCall, BatchedItems, BatchedValues, Endpoint, EndpointKind, MSAccThread, MetricsSource,
+    NameType, Number, ParsedExprs, Rpc, RpcClient, SystemVersion, TOTAL_MESSAGE_QUEUE_LEN_EXPR,
+};
+use crate::ssh::SshTunnel;
+use crate::ws::WsServer;
+use crate::{CollectArgs, Command, ReplayArgs, RunArgs};
 use anyhow::Context;
+use clap::ValueEnum;
+use erl_dist::node::NodeName;
 use serde::{Deserialize, Serialize};
 use smol::fs::File;
 use smol::io::AsyncWriteExt;
 use std::collections::BTreeMap;
 use std::io::BufRead;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
-type MetricsReceiver = mpsc::Receiver<Metrics>;
-type MetricsSender = mpsc::Sender<Metrics>;
+/// The name of the metric that aggregates the utilization of all scheduler threads.
+pub const TOTAL_UTILIZATION_METRIC_NAME: &str = "utilization.total";
+
+/// The name of the metric that holds the uptime of a node in milliseconds.
+pub const UPTIME_METRIC_NAME: &str = "statistics.wall_clock_ms";
+
+/// The name of the metric that holds the number of reductions.
+pub const REDUCTIONS_METRIC_NAME: &str = "statistics.exact_reductions";
+
+/// The name of the metric that holds the size of the binary heap.
+pub const BINARY_MEMORY_METRIC_NAME: &str = "memory.binary_bytes";
+
+/// The name of the parent of [`BINARY_MEMORY_METRIC_NAME`].
+pub const TOTAL_MEMORY_METRIC_NAME: &str = "memory.total_bytes";
+
+/// The name of the metric that holds the total run queue length, whose children are the lengths of each run queue.
+pub const RUN_QUEUE_METRIC_NAME: &str = "statistics.run_queue";
+
+/// The name of the metric collected by `--mailbox-metric`.
+pub const TOTAL_MESSAGE_QUEUE_LEN_METRIC_NAME: &str = "statistics.total_message_queue_len";
+
+/// The name of the metric collected by `--self-metrics`.
+pub const POLL_DURATION_METRIC_NAME: &str = "erldash.poll_duration_ms";
+
+/// `system_info/1` and `statistics/1` items that are missing on older OTP releases,
+/// along with the first release that supports them.
+const OTP_RELEASE_REQUIREMENTS: &[(&str, u32)] = &[
+    ("atom_count", 20),
+    ("atom_limit", 20),
+    ("run_queue_lengths_all", 20),
+    ("total_active_tasks_all", 20),
+    ("total_run_queue_lengths_all", 20),
+    ("ets_count", 21),
+];
+
+/// Resources whose `system_info.*_count` metrics have a `system_info.*_limit` counterpart.
+pub const LIMITED_RESOURCES: &[&str] = &["process", "port", "atom", "ets"];
+
+// How often a paused polling thread checks whether it has been resumed.
+const PAUSED_POLLING_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+const SCHEDULER_THREAD_TYPES: &[&str] = &["scheduler", "dirty_cpu_scheduler", "dirty_io_scheduler"];
+
+// Metrics are sent along with the index and the generation of the node that they were collected from.
+type MetricsReceiver = mpsc::Receiver<(usize, u64, Metrics)>;
+type MetricsSender = mpsc::Sender<(usize, u64, Metrics)>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metrics {
@@ -18,6 +72,21 @@ pub struct Metrics {
     pub items: BTreeMap<String, MetricValue>,
 }
 
+/// A labeled point in time written to the record file with the 'm' key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub timestamp: Duration,
+    pub marker: String,
+}
+
+/// A line of a record file following the header.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RecordEntry {
+    Metrics(Metrics),
+    Marker(Marker),
+}
+
 impl Metrics {
     fn new(start: Instant) -> Self {
         Self {
@@ -46,7 +115,7 @@ impl Metrics {
     {
         self.items
             .iter()
-            .filter(move |(_, v)| v.parent().as_ref().map_or(false, |&x| x == parent))
+            .filter(move |(_, v)| v.parent().as_ref().is_some_and(|&x| x == parent))
             .map(|(k, v)| (k.as_str(), v))
     }
 
@@ -73,7 +142,7 @@ impl Metrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MetricValue {
     Gauge {
-        value: u64,
+        value: i64,
         parent: Option<String>,
     },
     Counter {
@@ -85,6 +154,10 @@ pub enum MetricValue {
         value: f64,
         parent: Option<String>,
     },
+    FloatGauge {
+        value: f64,
+        parent: Option<String>,
+    },
 }
 
 impl MetricValue {
@@ -103,14 +176,32 @@ impl MetricValue {
     }
 
     fn gauge(value: u64) -> Self {
+        Self::signed_gauge(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+
+    fn gauge_with_parent(value: u64, parent: &str) -> Self {
+        Self::Gauge {
+            value: i64::try_from(value).unwrap_or(i64::MAX),
+            parent: Some(parent.to_owned()),
+        }
+    }
+
+    pub fn signed_gauge(value: i64) -> Self {
         Self::Gauge {
             value,
             parent: None,
         }
     }
 
-    fn gauge_with_parent(value: u64, parent: &str) -> Self {
-        Self::Gauge {
+    pub fn float_gauge(value: f64) -> Self {
+        Self::FloatGauge {
+            value,
+            parent: None,
+        }
+    }
+
+    fn float_gauge_with_parent(value: f64, parent: &str) -> Self {
+        Self::FloatGauge {
             value,
             parent: Some(parent.to_owned()),
         }
@@ -138,6 +229,30 @@ impl MetricValue {
             Self::Counter { value: Some(v), .. } => Some(v.round()),
             Self::Counter { .. } => None,
             Self::Utilization { value, .. } => Some(*value),
+            Self::FloatGauge { value, .. } => Some(*value),
+        }
+    }
+
+    /// Formats the value of the metric `name` for display.
+    ///
+    /// Unlike `to_string()`, this uses human-readable units for byte counters.
+    pub fn format(&self, name: &str) -> String {
+        match self {
+            Self::Counter {
+                value: Some(value), ..
+            } if is_bytes_metric(name) => format_bytes(value.round() as u64, "/s"),
+            Self::Counter {
+                value: Some(value), ..
+            } if is_cpu_time_metric(name) => format_micros_as_secs(*value, "/s"),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Returns the cumulative value of a counter (e.g., the total reductions since the node started).
+    pub fn raw_value(&self) -> Option<u64> {
+        match self {
+            Self::Counter { raw_value, .. } => Some(*raw_value),
+            _ => None,
         }
     }
 
@@ -146,6 +261,7 @@ impl MetricValue {
             Self::Gauge { parent, .. } => parent.as_ref().map(|x| x.as_str()),
             Self::Counter { parent, .. } => parent.as_ref().map(|x| x.as_str()),
             Self::Utilization { parent, .. } => parent.as_ref().map(|x| x.as_str()),
+            Self::FloatGauge { parent, .. } => parent.as_ref().map(|x| x.as_str()),
         }
     }
 }
@@ -154,10 +270,13 @@ impl std::fmt::Display for MetricValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Gauge { value, .. } => {
-                write!(f, "{}", format_u64(*value, "  "))
+                write!(f, "{}", format_i64(*value, "  "))
             }
             Self::Utilization { value, .. } => {
-                write!(f, "{:.1} %", value)
+                write!(f, "{}", format_utilization(*value))
+            }
+            Self::FloatGauge { value, .. } => {
+                write!(f, "{}", format_f64(*value, "  "))
             }
             Self::Counter {
                 value: Some(value), ..
@@ -171,55 +290,145 @@ impl std::fmt::Display for MetricValue {
     }
 }
 
-impl std::ops::AddAssign for MetricValue {
-    fn add_assign(&mut self, rhs: Self) {
-        match (self, rhs) {
-            (Self::Gauge { value, .. }, Self::Gauge { value: rhs, .. }) => {
-                *value += rhs;
-            }
-            (Self::Utilization { value, .. }, Self::Utilization { value: rhs, .. }) => {
-                *value += rhs;
-            }
-            (Self::Counter { value: lhs, .. }, Self::Counter { value: rhs, .. }) => {
-                if let (Some(lhs), Some(rhs)) = (lhs.as_mut(), rhs) {
-                    *lhs += rhs;
-                } else {
-                    *lhs = rhs;
-                }
-            }
-            (lhs, rhs) => {
-                panic!("cannot apply `MetricValue::add_assign()` to {lhs:?} and {rhs:?}",);
-            }
+/// Thousands separator style used by `format_u64()`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum NumberFormat {
+    /// `1,234,567`
+    #[default]
+    Comma,
+
+    /// `1.234.567`
+    Dot,
+
+    /// `1 234 567`
+    Space,
+
+    /// `1234567`
+    None,
+}
+
+impl NumberFormat {
+    fn separator(self) -> Option<u8> {
+        match self {
+            Self::Comma => Some(b','),
+            Self::Dot => Some(b'.'),
+            Self::Space => Some(b' '),
+            Self::None => None,
         }
     }
 }
 
-impl std::ops::SubAssign for MetricValue {
-    fn sub_assign(&mut self, rhs: Self) {
-        match (self, rhs) {
-            (Self::Gauge { value, .. }, Self::Gauge { value: rhs, .. }) => {
-                *value -= rhs;
-            }
-            (Self::Utilization { value, .. }, Self::Utilization { value: rhs, .. }) => {
-                *value -= rhs;
-            }
-            (Self::Counter { value: lhs, .. }, Self::Counter { value: rhs, .. }) => {
-                if let (Some(lhs), Some(rhs)) = (lhs.as_mut(), rhs) {
-                    *lhs -= rhs;
-                }
-            }
-            (lhs, rhs) => {
-                panic!("cannot apply `MetricValue::sub_assign()` to {lhs:?} and {rhs:?}",);
-            }
+static NUMBER_FORMAT: OnceLock<NumberFormat> = OnceLock::new();
+
+/// Sets the number format used by `format_u64()`.
+///
+/// This can be called only once, and subsequent calls are ignored.
+pub fn set_number_format(format: NumberFormat) {
+    let _ = NUMBER_FORMAT.set(format);
+}
+
+static UTILIZATION_DECIMALS: OnceLock<usize> = OnceLock::new();
+
+/// Sets the number of decimal places used by `format_utilization()`.
+///
+/// This can be called only once, and subsequent calls are ignored.
+pub fn set_utilization_decimals(decimals: usize) {
+    let _ = UTILIZATION_DECIMALS.set(decimals);
+}
+
+pub fn utilization_decimals() -> usize {
+    UTILIZATION_DECIMALS.get().copied().unwrap_or(1)
+}
+
+pub fn format_utilization(value: f64) -> String {
+    format!("{value:.*} %", utilization_decimals())
+}
+
+static FLOAT_DECIMALS: OnceLock<usize> = OnceLock::new();
+
+/// Sets the number of decimal places used by `format_f64()`.
+///
+/// This can be called only once, and subsequent calls are ignored.
+pub fn set_float_decimals(decimals: usize) {
+    let _ = FLOAT_DECIMALS.set(decimals);
+}
+
+pub fn float_decimals() -> usize {
+    FLOAT_DECIMALS.get().copied().unwrap_or(2)
+}
+
+/// Formats a floating-point gauge with the thousands separator of `format_u64()` in the integer part.
+pub fn format_f64(value: f64, suffix: &str) -> String {
+    let decimals = float_decimals();
+    let s = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = s.split_once('.').unwrap_or((&s, ""));
+    let sign = if value < 0.0 && s.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+        "-"
+    } else {
+        ""
+    };
+    let integer = format_u64(integer.parse().unwrap_or(u64::MAX), "");
+    if fraction.is_empty() {
+        format!("{sign}{integer}{suffix}")
+    } else {
+        format!("{sign}{integer}.{fraction}{suffix}")
+    }
+}
+
+pub fn format_i64(n: i64, suffix: &str) -> String {
+    if n < 0 {
+        format!("-{}", format_u64(n.unsigned_abs(), suffix))
+    } else {
+        format_u64(n as u64, suffix)
+    }
+}
+
+/// Prefix of the counters of the CPU time consumed by each thread type, in microseconds.
+const CPU_TIME_METRIC_PREFIX: &str = "cpu_time.";
+
+/// Returns `true` if the metric `name` counts CPU time in microseconds, which is shown in seconds.
+pub fn is_cpu_time_metric(name: &str) -> bool {
+    name.starts_with(CPU_TIME_METRIC_PREFIX)
+}
+
+/// Formats microseconds as seconds (e.g., `1.60 s`).
+pub fn format_micros_as_secs(micros: f64, suffix: &str) -> String {
+    format_f64(micros / 1_000_000.0, &format!(" s{suffix}"))
+}
+
+/// Returns `true` if the metric `name` counts bytes.
+pub fn is_bytes_metric(name: &str) -> bool {
+    name.starts_with("statistics.io.")
+}
+
+/// Formats a byte count with binary units (e.g., `3.2 MiB`).
+pub fn format_bytes(n: u64, suffix: &str) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if n < 1024 {
+        return format!("{n} B{suffix}");
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
         }
+        value /= 1024.0;
+        unit = next_unit;
     }
+    format!("{value:.1} {unit}{suffix}")
 }
 
-pub fn format_u64(mut n: u64, suffix: &str) -> String {
+pub fn format_u64(n: u64, suffix: &str) -> String {
+    format_u64_with(NUMBER_FORMAT.get().copied().unwrap_or_default(), n, suffix)
+}
+
+fn format_u64_with(format: NumberFormat, mut n: u64, suffix: &str) -> String {
+    let separator = format.separator();
     let mut s = Vec::new();
     for i in 0.. {
-        if i % 3 == 0 && i != 0 {
-            s.push(b',');
+        if let Some(separator) = separator.filter(|_| i % 3 == 0 && i != 0) {
+            s.push(separator);
         }
         let m = n % 10;
         s.push(b'0' + m as u8);
@@ -245,6 +454,12 @@ impl MetricsPoller {
         match command {
             Command::Run(args) => RealtimeMetricsPoller::start_thread(args).map(Self::Realtime),
             Command::Replay(args) => ReplayMetricsPoller::new(args).map(Self::Replay),
+            Command::Dump(_) => {
+                anyhow::bail!("`dump` command does not start a metrics poller")
+            }
+            Command::Nodes(_) => {
+                anyhow::bail!("`nodes` command does not start a metrics poller")
+            }
         }
     }
 
@@ -252,41 +467,202 @@ impl MetricsPoller {
         matches!(self, Self::Replay(_))
     }
 
+    /// Returns the header of the first node.
     pub fn header(&self) -> &Header {
         match self {
-            Self::Realtime(poller) => &poller.header,
+            Self::Realtime(poller) => &poller.nodes[0].header,
             Self::Replay(poller) => &poller.header,
         }
     }
 
-    pub fn poll_metrics(&self, timeout: Duration) -> Result<Metrics, mpsc::RecvTimeoutError> {
+    /// Returns the headers of all nodes (the index of a header is the node index given by `poll_metrics()`).
+    pub fn headers(&self) -> Vec<Header> {
+        match self {
+            Self::Realtime(poller) => poller.nodes.iter().map(|n| n.header.clone()).collect(),
+            Self::Replay(poller) => std::iter::once(poller)
+                .chain(poller.compare.as_deref())
+                .map(|p| p.header.clone())
+                .collect(),
+        }
+    }
+
+    /// Returns the warnings that occurred while starting the poller.
+    pub fn warnings(&self) -> Vec<String> {
         match self {
-            Self::Realtime(poller) => poller.rx.recv_timeout(timeout),
+            Self::Realtime(poller) => poller
+                .nodes
+                .iter()
+                .flat_map(|n| n.warnings.iter().cloned())
+                .collect(),
+            Self::Replay(_) => Vec::new(),
+        }
+    }
+
+    /// Polls the next metrics along with the index of the node that they were collected from.
+    pub fn poll_metrics(
+        &self,
+        timeout: Duration,
+    ) -> Result<(usize, Metrics), mpsc::RecvTimeoutError> {
+        match self {
+            Self::Realtime(poller) => loop {
+                match poller.rx.recv_timeout(timeout) {
+                    Ok((node_index, generation, metrics)) => {
+                        if poller.nodes[node_index].generation != generation {
+                            log::debug!(
+                                "discarded metrics of a node that has been switched away from"
+                            );
+                            continue;
+                        }
+                        return Ok((node_index, metrics));
+                    }
+                    // As the poller itself holds a sender, the channel is never disconnected.
+                    Err(mpsc::RecvTimeoutError::Timeout) if poller.is_terminated() => {
+                        return Err(mpsc::RecvTimeoutError::Disconnected);
+                    }
+                    Err(e) => return Err(e),
+                }
+            },
             Self::Replay(_) => {
                 unreachable!()
             }
         }
     }
 
+    /// Connects to `erlang_node` and replaces the node at `node_index` with it.
+    ///
+    /// If the connection fails, the current node is kept.
+    pub fn switch_node(
+        &mut self,
+        node_index: usize,
+        erlang_node: NodeName,
+    ) -> anyhow::Result<Header> {
+        match self {
+            Self::Realtime(poller) => poller.switch_node(node_index, erlang_node),
+            Self::Replay(_) => anyhow::bail!("switching nodes is not supported in replay mode"),
+        }
+    }
+
+    /// Returns the `--duration` value of a realtime poller.
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Realtime(poller) => poller.duration,
+            Self::Replay(_) => None,
+        }
+    }
+
+    /// Returns `true` if the polling threads have stopped because `--duration` has been reached.
+    pub fn is_finished(&self) -> bool {
+        match self {
+            Self::Realtime(poller) => poller.control.finished.load(Ordering::SeqCst),
+            Self::Replay(_) => false,
+        }
+    }
+
+    /// Returns `None` if metrics are not being recorded, otherwise whether the recording is paused.
+    pub fn is_recording_paused(&self) -> Option<bool> {
+        match self {
+            Self::Realtime(poller) if poller.recording => {
+                Some(poller.control.recording_paused.load(Ordering::SeqCst))
+            }
+            _ => None,
+        }
+    }
+
+    /// Pauses or resumes writing polled metrics to the `--record` file.
+    pub fn toggle_recording(&self) {
+        if let Self::Realtime(poller) = self {
+            poller
+                .control
+                .recording_paused
+                .fetch_xor(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Suspends or resumes the RPCs of the polling threads.
+    pub fn set_polling_paused(&self, paused: bool) {
+        if let Self::Realtime(poller) = self {
+            poller
+                .control
+                .polling_paused
+                .store(paused, Ordering::SeqCst);
+        }
+    }
+
+    /// Writes a marker with the given label to the `--record` file.
+    pub fn add_marker(&self, label: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Realtime(poller) if poller.recording => {
+                poller
+                    .control
+                    .pending_markers
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push((Instant::now(), label.to_owned()));
+                Ok(())
+            }
+            _ => anyhow::bail!("markers can only be added while recording"),
+        }
+    }
+
+    /// Returns the markers of the replayed record file in chronological order.
+    pub fn markers(&self) -> &[Marker] {
+        match self {
+            Self::Realtime(_) => &[],
+            Self::Replay(poller) => &poller.markers,
+        }
+    }
+
+    /// Returns `true` if the replayed record file is followed as it grows (`replay --follow`).
+    pub fn is_following(&self) -> bool {
+        matches!(self, Self::Replay(poller) if poller.follow.is_some())
+    }
+
+    /// Reads the metrics and markers appended to the followed record file.
+    ///
+    /// Returns `true` if new metrics have been read.
+    pub fn follow_replay(&mut self) -> anyhow::Result<bool> {
+        match self {
+            Self::Realtime(_) => Ok(false),
+            Self::Replay(poller) => poller.follow(),
+        }
+    }
+
+    /// Returns `true` if a second record file is replayed as node 1 for comparison.
+    pub fn is_compare(&self) -> bool {
+        matches!(self, Self::Replay(poller) if poller.compare.is_some())
+    }
+
+    /// Returns the last timestamp among the replayed record files.
     pub fn replay_last_time(&self) -> Duration {
         match self {
             Self::Realtime(_) => Duration::from_secs(0),
-            Self::Replay(poller) => poller
-                .metrics_log
-                .last()
+            Self::Replay(poller) => std::iter::once(poller)
+                .chain(poller.compare.as_deref())
+                .filter_map(|p| p.metrics_log.last())
                 .map(|m| m.timestamp)
+                .max()
                 .unwrap_or_default(),
         }
     }
 
+    /// Returns the replayed metrics of the given node within `[start_time, end_time]`.
     pub fn get_metrics_range(
         &self,
+        node_index: usize,
         start_time: Duration,
         end_time: Duration,
     ) -> anyhow::Result<impl '_ + Iterator<Item = &Metrics>> {
         let Self::Replay(poller) = self else {
             anyhow::bail!("`get_metrics_range()` is only available in replay mode");
         };
+        let poller = match node_index {
+            0 => poller,
+            1 => poller
+                .compare
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("no record file to compare"))?,
+            _ => anyhow::bail!("unknown node index: {node_index}"),
+        };
         Ok(poller.metrics_log.iter().filter(move |metrics| {
             let time = metrics.timestamp;
             start_time <= time && time <= end_time
@@ -294,162 +670,786 @@ impl MetricsPoller {
     }
 }
 
+/// A group of metrics that can be selectively collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, Deserialize)]
+#[value(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MetricGroup {
+    /// `system_info.*` metrics.
+    SystemInfo,
+
+    /// `statistics.*` metrics other than IO and run queues.
+    Statistics,
+
+    /// `statistics.io.*` metrics.
+    Io,
+
+    /// `memory.*` metrics.
+    Memory,
+
+    /// `utilization.*` and `cpu_time.*` metrics (microstate accounting).
+    Msacc,
+
+    /// `statistics.run_queue.*` metrics.
+    RunQueue,
+}
+
+impl MetricGroup {
+    /// Returns the `erlang:statistics/1` and `erlang:system_info/1` items queried for this group.
+    fn items(self) -> (&'static [&'static str], &'static [&'static str]) {
+        match self {
+            Self::SystemInfo => (
+                &[],
+                &[
+                    "process_count",
+                    "port_count",
+                    "atom_count",
+                    "ets_count",
+                    "process_limit",
+                    "port_limit",
+                    "atom_limit",
+                    "ets_limit",
+                    "schedulers",
+                    "schedulers_online",
+                    "dirty_cpu_schedulers",
+                    "logical_processors",
+                ],
+            ),
+            Self::Statistics => (
+                &[
+                    "context_switches",
+                    "exact_reductions",
+                    "garbage_collection",
+                    "runtime",
+                    "wall_clock",
+                    "total_active_tasks_all",
+                    "total_run_queue_lengths_all",
+                ],
+                &[],
+            ),
+            Self::Io => (&["io"], &[]),
+            Self::RunQueue => (
+                &[
+                    "run_queue_lengths_all",
+                    "run_queue_lengths",
+                    "total_run_queue_lengths",
+                ],
+                &[],
+            ),
+            Self::Memory | Self::Msacc => (&[], &[]),
+        }
+    }
+}
+
+/// Returns the items of the enabled metric groups that are available on the node,
+/// so that the batched RPC doesn't fail on items that are filtered out or unsupported.
+fn batched_items(args: &CollectArgs, unavailable_items: &[&'static str]) -> BatchedItems {
+    let mut items = BatchedItems::default();
+    for &group in MetricGroup::value_variants() {
+        if !args.is_metric_group_enabled(group) {
+            continue;
+        }
+        let (statistics, system_info) = group.items();
+        let available = |item: &&'static str| !unavailable_items.contains(item);
+        items
+            .statistics
+            .extend(statistics.iter().copied().filter(available));
+        items
+            .system_info
+            .extend(system_info.iter().copied().filter(available));
+        items.memory |= group == MetricGroup::Memory;
+    }
+    items
+}
+
+/// An Erlang expression given by `--watch LABEL=EXPR`.
+#[derive(Debug, Clone)]
+pub struct WatchExpr {
+    pub label: String,
+    pub expr: String,
+}
+
+impl WatchExpr {
+    fn metric_name(&self) -> String {
+        format!("custom.watch.{}", self.label)
+    }
+}
+
+impl std::str::FromStr for WatchExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((label, expr)) = s.split_once('=') else {
+            anyhow::bail!("expected `LABEL=EXPR`, but got {s:?}");
+        };
+        let label = label.trim();
+        anyhow::ensure!(
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "label must consist of alphanumeric characters, `_` or `-`, but got {label:?}"
+        );
+        let expr = expr.trim().trim_end_matches('.');
+        anyhow::ensure!(!expr.is_empty(), "expression is empty");
+        Ok(Self {
+            label: label.to_owned(),
+            expr: format!("{expr}."),
+        })
+    }
+}
+
+/// Version of the record file format written by this version of erldash.
+///
+/// Files written before the version was introduced are read as version 0.
+pub const RECORD_FORMAT_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Header {
+    #[serde(default)]
+    pub format_version: u32,
     pub system_version: SystemVersion,
     pub node_name: String,
     pub start_time: chrono::DateTime<chrono::Local>,
+
+    /// Static facts about the node shown by the 'i' key (empty in record files of older versions).
+    #[serde(default)]
+    pub node_info: Vec<(String, String)>,
+
+    // Only known while connected (not recorded).
+    #[serde(skip)]
+    pub endpoint: Option<Endpoint>,
+}
+
+impl Header {
+    /// Returns the wall-clock time of the given offset from the start of the run (e.g., [`Metrics::timestamp`]).
+    pub fn wall_clock(&self, offset: Duration) -> chrono::DateTime<chrono::Local> {
+        self.start_time + offset
+    }
 }
 
 #[derive(Debug)]
 pub struct ReplayMetricsPoller {
     header: Header,
     metrics_log: Vec<Metrics>,
+    markers: Vec<Marker>,
+
+    // `--compare` record file.
+    compare: Option<Box<Self>>,
+
+    // Kept open to read the entries appended to the record file if `--follow` is specified.
+    follow: Option<RecordReader>,
 }
 
 impl ReplayMetricsPoller {
     fn new(args: ReplayArgs) -> anyhow::Result<Self> {
-        let record_file_path = args.file;
-        let file = std::fs::File::open(&record_file_path).with_context(|| {
+        let mut poller = Self::load(&args.file, args.follow)?;
+        if let Some(path) = &args.compare {
+            poller.compare = Some(Box::new(Self::load(path, false)?));
+        }
+        Ok(poller)
+    }
+
+    fn load(record_file_path: &std::path::Path, follow: bool) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(record_file_path).with_context(|| {
             format!("failed to open record file: {}", record_file_path.display())
         })?;
-        let reader = std::io::BufReader::new(file);
-
-        let mut header = None;
-        let mut metrics_log = Vec::new();
-        for (i, line) in reader.lines().enumerate() {
-            let line = line?;
-            if i == 0 {
-                header = Some(
-                    serde_json::from_str(&line)
-                        .with_context(|| format!("failed to parse record file: line={}", i + 1))?,
-                );
-                continue;
-            }
-            let metrics = serde_json::from_str(&line)
-                .with_context(|| format!("failed to parse record file: line={}", i + 1))?;
-            metrics_log.push(metrics);
-        }
-        let header = header.ok_or_else(|| anyhow::anyhow!("record file is empty"))?;
-        Ok(Self {
+        let mut reader = RecordReader {
+            reader: std::io::BufReader::new(file),
+            pending: String::new(),
+            line_number: 0,
+        };
+
+        let (i, line) = reader
+            .next_line()?
+            .or_else(|| reader.take_pending())
+            .ok_or_else(|| anyhow::anyhow!("record file is empty"))?;
+        let header = serde_json::from_str(&line)
+            .with_context(|| format!("failed to parse record file: line={i}"))?;
+        let mut poller = Self {
             header,
-            metrics_log,
-        })
+            metrics_log: Vec::new(),
+            markers: Vec::new(),
+            compare: None,
+            follow: None,
+        };
+        poller.read_entries(&mut reader)?;
+        if follow {
+            poller.follow = Some(reader);
+        } else if let Some((i, line)) = reader.take_pending() {
+            // Without `--follow`, the last line is complete even if it lacks a newline.
+            poller.push_entry(i, &line)?;
+        }
+        Ok(poller)
+    }
+
+    /// Reads the complete lines available in `reader` and returns the number of new metrics.
+    fn read_entries(&mut self, reader: &mut RecordReader) -> anyhow::Result<usize> {
+        let old_len = self.metrics_log.len();
+        while let Some((i, line)) = reader.next_line()? {
+            self.push_entry(i, &line)?;
+        }
+        self.markers.sort_by_key(|m| m.timestamp);
+        Ok(self.metrics_log.len() - old_len)
+    }
+
+    fn push_entry(&mut self, line_number: usize, line: &str) -> anyhow::Result<()> {
+        let entry = serde_json::from_str(line)
+            .with_context(|| format!("failed to parse record file: line={line_number}"))?;
+        match entry {
+            RecordEntry::Metrics(metrics) => self.metrics_log.push(metrics),
+            RecordEntry::Marker(marker) => self.markers.push(marker),
+        }
+        Ok(())
+    }
+
+    /// Reads the entries appended to the record file since the last call if `--follow` is specified.
+    fn follow(&mut self) -> anyhow::Result<bool> {
+        let Some(mut reader) = self.follow.take() else {
+            return Ok(false);
+        };
+        let result = self.read_entries(&mut reader);
+        self.follow = Some(reader);
+        Ok(result? > 0)
     }
 }
 
+/// Reads a record file line by line, including lines appended after the end of the file was reached.
 #[derive(Debug)]
-pub struct RealtimeMetricsPoller {
-    rx: MetricsReceiver,
-    header: Header,
-    rpc_client: RpcClient,
-    old_microstate_accounting_flag: bool,
+struct RecordReader {
+    reader: std::io::BufReader<std::fs::File>,
+
+    // The last line read so far if it is not terminated by a newline yet (i.e., it is still being written).
+    pending: String,
+
+    line_number: usize,
 }
 
-impl RealtimeMetricsPoller {
-    fn start_thread(args: RunArgs) -> anyhow::Result<Self> {
-        MetricsPollerThread::start_thread(args)
+impl RecordReader {
+    /// Returns the next newline-terminated line with its (1-based) line number.
+    ///
+    /// `None` is returned at the end of the file, and a partially written line is kept until its newline arrives.
+    fn next_line(&mut self) -> anyhow::Result<Option<(usize, String)>> {
+        self.reader.read_line(&mut self.pending)?;
+        if !self.pending.ends_with('\n') {
+            return Ok(None);
+        }
+        let line = std::mem::take(&mut self.pending);
+        self.line_number += 1;
+        let line = line.trim_end_matches('\n').trim_end_matches('\r');
+        Ok(Some((self.line_number, line.to_owned())))
     }
-}
 
-impl Drop for RealtimeMetricsPoller {
-    fn drop(&mut self) {
-        if !self.old_microstate_accounting_flag {
-            if let Err(e) = smol::block_on(
-                self.rpc_client
-                    .set_system_flag_bool("microstate_accounting", "false"),
-            ) {
-                log::warn!("faild to disable microstate accounting: {e}");
-            } else {
-                log::debug!("disabled microstate accounting");
-            }
+    fn take_pending(&mut self) -> Option<(usize, String)> {
+        if self.pending.is_empty() {
+            return None;
         }
+        self.line_number += 1;
+        Some((self.line_number, std::mem::take(&mut self.pending)))
     }
 }
 
 #[derive(Debug)]
-struct MetricsPollerThread {
-    args: RunArgs,
-    rpc_client: RpcClient,
+pub struct RealtimeMetricsPoller<C: Rpc = RpcClient> {
+    args: Box<RunArgs>,
+    rx: MetricsReceiver,
+
+    // Used to start polling threads for nodes switched to at runtime.
     tx: MetricsSender,
-    prev_metrics: Metrics,
-    start: Instant,
-    header: Header,
-    record_file: Option<File>,
+
+    nodes: Vec<RealtimeNode<C>>,
+    duration: Option<Duration>,
+    recording: bool,
+    control: Arc<PollerControl>,
 }
 
-impl MetricsPollerThread {
-    fn start_thread(args: RunArgs) -> anyhow::Result<RealtimeMetricsPoller> {
-        let (tx, rx) = mpsc::channel();
+/// State shared between [`RealtimeMetricsPoller`] and its polling threads.
+#[derive(Debug, Default)]
+struct PollerControl {
+    /// Set when a polling thread stops because `--duration` has been reached.
+    finished: AtomicBool,
 
-        let rpc_client: RpcClient = smol::block_on(async {
-            let cookie = args.find_cookie()?;
-            let client = RpcClient::connect(&args.erlang_node, args.port, &cookie).await?;
-            Ok(client) as anyhow::Result<_>
-        })?;
-        let system_version = smol::block_on(rpc_client.get_system_version())?;
-        let old_microstate_accounting_flag =
-            smol::block_on(rpc_client.set_system_flag_bool("microstate_accounting", "true"))?;
-        log::debug!(
-            "enabled microstate accounting (old flag state is {old_microstate_accounting_flag})"
-        );
+    /// Whether polled metrics are currently not written to the `--record` file.
+    recording_paused: AtomicBool,
 
-        let header = Header {
-            system_version: system_version.clone(),
-            node_name: args.erlang_node.to_string(),
-            start_time: chrono::Local::now(),
-        };
-        let poller = RealtimeMetricsPoller {
-            rx,
-            header: header.clone(),
-            rpc_client: rpc_client.clone(),
-            old_microstate_accounting_flag,
-        };
+    /// Whether the polling threads are suspended (`--pause-on-blur`).
+    polling_paused: AtomicBool,
 
-        let record_file = if let Some(path) = &args.record {
-            Some(File::from(std::fs::File::create(path).with_context(
-                || format!("failed to record file {}", path.display()),
-            )?))
-        } else {
-            None
-        };
+    /// Markers that have not been written to the `--record` file yet.
+    pending_markers: Mutex<Vec<(Instant, String)>>,
+
+    /// Server for `--ws-listen`.
+    ws_server: Option<WsServer>,
+}
+
+impl<C: Rpc> RealtimeMetricsPoller<C> {
+    fn start_thread(mut args: RunArgs) -> anyhow::Result<Self> {
+        resolve_nodes(&mut args.collect)?;
+        anyhow::ensure!(
+            args.collect
+                .msacc_window
+                .is_none_or(|w| w <= args.collect.polling_interval()),
+            "`--msacc-window` must not be longer than the polling interval"
+        );
+        anyhow::ensure!(
+            args.record.is_none() || args.collect.erlang_nodes.len() == 1,
+            "`--record` is only supported when a single node is specified"
+        );
 
-        std::thread::spawn(|| {
-            let start = Instant::now();
-            Self {
-                args,
-                rpc_client,
-                tx,
-                prev_metrics: Metrics::new(start),
-                start,
-                header,
-                record_file,
-            }
-            .run()
+        let (tx, rx) = mpsc::channel();
+        let ws_server = args.ws_listen.map(WsServer::start).transpose()?;
+        let control = Arc::new(PollerControl {
+            ws_server,
+            ..Default::default()
         });
-        Ok(poller)
+        let mut nodes = Vec::new();
+        let mut last_error = None;
+        for erlang_node in &args.collect.erlang_nodes {
+            match MetricsPollerThread::<C>::start_thread(
+                &args,
+                erlang_node.clone(),
+                nodes.len(),
+                tx.clone(),
+                control.clone(),
+            ) {
+                Ok(node) => nodes.push(node),
+                Err(e) if args.collect.erlang_nodes.len() > 1 => {
+                    log::warn!("failed to connect to {erlang_node}: {e}");
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if nodes.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| anyhow::anyhow!("no Erlang node is specified"))
+                .context("failed to connect to any of the specified nodes"));
+        }
+        Ok(Self {
+            duration: args.duration,
+            recording: args.record.is_some(),
+            args: Box::new(args),
+            rx,
+            tx,
+            nodes,
+            control,
+        })
+    }
+
+    fn switch_node(&mut self, node_index: usize, erlang_node: NodeName) -> anyhow::Result<Header> {
+        anyhow::ensure!(
+            !self.recording,
+            "switching nodes is not supported while recording"
+        );
+        let (mut node, mut thread) = MetricsPollerThread::<C>::connect(
+            self.args.collect.clone(),
+            erlang_node,
+            node_index,
+            self.tx.clone(),
+        )?;
+
+        // The old thread is not joined as it may be blocked by an unresponsive node. Instead, the metrics
+        // it sends after this are discarded by `poll_metrics()` as their generation is outdated.
+        self.nodes[node_index].stop.store(true, Ordering::SeqCst);
+        node.generation = self.nodes[node_index].generation + 1;
+        thread.generation = node.generation;
+        let node = thread.spawn(node, self.control.clone(), &self.args)?;
+        let header = node.header.clone();
+        let mut old_node = std::mem::replace(&mut self.nodes[node_index], node);
+
+        // The old node restores its microstate accounting flag when dropped, which must not happen while
+        // another connection to the same node (e.g., the new one when switching to the same node) relies on
+        // it. In that case, that connection takes over the restoration of the original flag.
+        if let Some(flag) = old_node.old_microstate_accounting_flag {
+            if let Some(other) = self.nodes.iter_mut().find(|node| {
+                node.header.node_name == old_node.header.node_name
+                    && node.old_microstate_accounting_flag.is_some()
+            }) {
+                other.old_microstate_accounting_flag = Some(flag);
+                old_node.old_microstate_accounting_flag = None;
+            }
+        }
+        Ok(header)
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.nodes
+            .iter()
+            .all(|node| node.thread.as_ref().is_none_or(|t| t.is_finished()))
+    }
+}
+
+/// Connects to the target node and collects metrics only once.
+///
+/// As counter rates need two samples, only the raw values of counter metrics are available.
+/// `C` is the client used to connect to the node (i.e., [`RpcClient`] except in tests).
+pub fn collect_once<C: Rpc>(mut args: CollectArgs) -> anyhow::Result<Metrics> {
+    resolve_nodes(&mut args)?;
+    let [erlang_node] = args.erlang_nodes.as_slice() else {
+        anyhow::bail!("exactly one node must be specified");
+    };
+    let erlang_node = erlang_node.clone();
+    let (tx, _rx) = mpsc::channel();
+
+    // `_node` restores the microstate accounting flag when dropped.
+    let (_node, mut thread) = MetricsPollerThread::<C>::connect(args, erlang_node, 0, tx)?;
+    smol::block_on(thread.poll_once())
+}
+
+fn resolve_nodes(args: &mut CollectArgs) -> anyhow::Result<()> {
+    if args.auto_node {
+        args.erlang_nodes = vec![smol::block_on(crate::erlang::find_local_node())?];
+    } else if args.erlang_nodes.is_empty() {
+        if let Some(path) = &args.vm_args {
+            let Some(name) = crate::erlang::VmArgs::load(path)?.name else {
+                anyhow::bail!(
+                    "no node names are specified and the vm.args file {} has no `-name` flag",
+                    path.display()
+                );
+            };
+            args.erlang_nodes = vec![name];
+        }
+    }
+    Ok(())
+}
+
+/// Writes the header and polled metrics to the `--record` file as JSON lines.
+#[derive(Debug)]
+struct Recorder {
+    file: File,
+}
+
+impl Recorder {
+    fn create(path: &std::path::Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to record file {}", path.display()))?;
+        Ok(Self {
+            file: File::from(file),
+        })
     }
 
     async fn write_json_line(&mut self, value: &impl serde::Serialize) -> anyhow::Result<()> {
-        if let Some(file) = &mut self.record_file {
-            let mut bytes = serde_json::to_vec(value)?;
-            bytes.push(b'\n');
-            file.write_all(&bytes).await?;
-            file.flush().await?;
+        let mut bytes = serde_json::to_vec(value)?;
+        bytes.push(b'\n');
+        self.file.write_all(&bytes).await?;
+        self.file.flush().await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RealtimeNode<C: Rpc = RpcClient> {
+    header: Header,
+    rpc_client: C,
+
+    // `None` if microstate accounting has not been enabled by `erldash`.
+    old_microstate_accounting_flag: Option<bool>,
+
+    warnings: Vec<String>,
+
+    // Tells the polling thread to stop.
+    stop: Arc<AtomicBool>,
+
+    // Incremented each time the node at the same index is switched to another one (see `switch_node()`).
+    generation: u64,
+
+    // `None` if no polling thread has been spawned (e.g., `collect_once()`).
+    thread: Option<std::thread::JoinHandle<()>>,
+
+    // Declared last so that the tunnel is still open while `drop()` restores the node's state.
+    _ssh_tunnel: Option<SshTunnel>,
+}
+
+impl<C: Rpc> Drop for RealtimeNode<C> {
+    fn drop(&mut self) {
+        if self.old_microstate_accounting_flag == Some(false) {
+            if let Err(e) = smol::block_on(
+                self.rpc_client
+                    .set_system_flag_bool("microstate_accounting", "false"),
+            ) {
+                log::warn!("faild to disable microstate accounting: {e}");
+            } else {
+                log::debug!("disabled microstate accounting");
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MetricsPollerThread<C: Rpc = RpcClient> {
+    args: CollectArgs,
+
+    // `--duration` of `run` (`None` for `collect_once()`).
+    duration: Option<Duration>,
+    node_index: usize,
+    generation: u64,
+    msacc_enabled: bool,
+    rpc_client: C,
+    batched_call: Option<BatchedCall>,
+    tx: MetricsSender,
+    prev_metrics: Metrics,
+    start: Instant,
+    header: Header,
+    recorder: Option<Recorder>,
+    control: Arc<PollerControl>,
+    stop: Arc<AtomicBool>,
+
+    // Items of `OTP_RELEASE_REQUIREMENTS` that are not available on the target node.
+    unavailable_items: Vec<&'static str>,
+
+    // `--watch` expressions parsed on the target node.
+    watches: Vec<(WatchExpr, ParsedExprs)>,
+
+    // Values of the `cpu_time.*` counters, which are accumulated from the utilization of each poll
+    // as the microstate accounting counters are reset after each poll.
+    cpu_times: BTreeMap<String, u64>,
+
+    // `Some` if `--mailbox-metric` is specified.
+    mailbox: Option<MailboxSampler>,
+}
+
+/// State of the `--mailbox-metric` sampling, which is less frequent than the polling.
+#[derive(Debug)]
+struct MailboxSampler {
+    exprs: ParsedExprs,
+    interval: Duration,
+    last: Option<(Instant, u64)>,
+}
+
+impl<C: Rpc> MetricsPollerThread<C> {
+    fn start_thread(
+        args: &RunArgs,
+        erlang_node: NodeName,
+        node_index: usize,
+        tx: MetricsSender,
+        control: Arc<PollerControl>,
+    ) -> anyhow::Result<RealtimeNode<C>> {
+        let (node, this) = Self::connect(args.collect.clone(), erlang_node, node_index, tx)?;
+        this.spawn(node, control, args)
+    }
+
+    /// Starts polling with the options of `run` that only apply to a polling thread (e.g., `--record`).
+    fn spawn(
+        mut self,
+        mut node: RealtimeNode<C>,
+        control: Arc<PollerControl>,
+        args: &RunArgs,
+    ) -> anyhow::Result<RealtimeNode<C>> {
+        self.control = control;
+        self.duration = args.duration;
+        self.recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+        node.thread = Some(std::thread::spawn(move || self.run()));
+        Ok(node)
+    }
+
+    fn connect(
+        args: CollectArgs,
+        erlang_node: NodeName,
+        node_index: usize,
+        tx: MetricsSender,
+    ) -> anyhow::Result<(RealtimeNode<C>, Self)> {
+        let cookie = args.find_cookie(&erlang_node)?;
+        // The name type is detected before the host is replaced for SSH port forwarding.
+        let name_type = NameType::detect(&erlang_node);
+        let ssh_tunnel = args
+            .ssh
+            .as_ref()
+            .map(|destination| SshTunnel::open_for_node(destination, &erlang_node, args.port))
+            .transpose()?;
+        let rpc_client: C = smol::block_on(async {
+            let client = if let Some(tunnel) = &ssh_tunnel {
+                // The host of a node name is only used as the address to connect to.
+                let local_node = NodeName::new(erlang_node.name(), "127.0.0.1")?;
+                let port = Some(tunnel.local_port());
+                C::connect(&local_node, name_type, port, &args.transport, &cookie).await?
+            } else {
+                C::connect(&erlang_node, name_type, args.port, &args.transport, &cookie).await?
+            };
+            Ok(client) as anyhow::Result<_>
+        })?;
+        let (port, kind) = match (&ssh_tunnel, &args.ssh) {
+            (Some(tunnel), Some(destination)) => {
+                (tunnel.remote_port(), EndpointKind::Ssh(destination.clone()))
+            }
+            _ if args.port.is_some() => (rpc_client.port(), EndpointKind::Direct),
+            _ => (rpc_client.port(), EndpointKind::Epmd),
+        };
+        let endpoint = Endpoint {
+            host: erlang_node.host().to_owned(),
+            port,
+            kind,
+        };
+        log::info!("connected to {erlang_node} at {endpoint}");
+        let system_version = smol::block_on(rpc_client.get_system_version())?;
+        let node_info = smol::block_on(rpc_client.get_node_info());
+        let unavailable_items = match system_version.otp_release() {
+            Some(release) => OTP_RELEASE_REQUIREMENTS
+                .iter()
+                .filter(|(_, since)| release < *since)
+                .map(|(item, _)| *item)
+                .collect::<Vec<_>>(),
+            None => {
+                log::warn!(
+                    "failed to parse the OTP release from {:?}",
+                    system_version.get()
+                );
+                Vec::new()
+            }
+        };
+        if !unavailable_items.is_empty() {
+            log::info!(
+                "skipping metrics that are not available on {erlang_node}: {}",
+                unavailable_items.join(", ")
+            );
+        }
+        let mut warnings = Vec::new();
+        let old_microstate_accounting_flag = if args.is_metric_group_enabled(MetricGroup::Msacc) {
+            match smol::block_on(rpc_client.set_system_flag_bool("microstate_accounting", "true")) {
+                Ok(old_flag) => {
+                    log::debug!("enabled microstate accounting (old flag state is {old_flag})");
+                    Some(old_flag)
+                }
+                Err(e) => {
+                    log::warn!("failed to enable microstate accounting: {e}");
+                    warnings.push(format!(
+                        "microstate accounting is not available on {erlang_node}"
+                    ));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // `Metrics::timestamp` is the offset from `start`, which is anchored to `start_time` so that
+        // `Header::wall_clock()` gives the time at which the metrics were collected.
+        let start = Instant::now();
+        let header = Header {
+            format_version: RECORD_FORMAT_VERSION,
+            system_version: system_version.clone(),
+            node_name: erlang_node.to_string(),
+            start_time: chrono::Local::now(),
+            node_info,
+            endpoint: Some(endpoint),
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let node = RealtimeNode {
+            header: header.clone(),
+            rpc_client: rpc_client.clone(),
+            old_microstate_accounting_flag,
+            warnings,
+            stop: stop.clone(),
+            generation: 0,
+            thread: None,
+            _ssh_tunnel: ssh_tunnel,
+        };
+
+        let batched_items = batched_items(&args, &unavailable_items);
+        let batched_call = if batched_items.is_empty() {
+            None
+        } else {
+            match smol::block_on(rpc_client.prepare_batched_call(&batched_items)) {
+                Ok(call) => Some(call),
+                Err(e) => {
+                    log::info!("batched RPC is not available on {erlang_node}: {e}");
+                    None
+                }
+            }
+        };
+
+        let watches = args
+            .watch
+            .iter()
+            .map(|watch| {
+                let exprs =
+                    smol::block_on(rpc_client.parse_exprs(&watch.expr)).with_context(|| {
+                        format!("failed to parse `--watch` expression: {}", watch.expr)
+                    })?;
+                Ok((watch.clone(), exprs))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mailbox = args
+            .mailbox_metric
+            .map(|interval| {
+                let exprs = smol::block_on(rpc_client.parse_exprs(TOTAL_MESSAGE_QUEUE_LEN_EXPR))?;
+                Ok(MailboxSampler {
+                    exprs,
+                    interval,
+                    last: None,
+                }) as anyhow::Result<_>
+            })
+            .transpose()?;
+
+        let this = Self {
+            args,
+            duration: None,
+            node_index,
+            generation: 0,
+            msacc_enabled: old_microstate_accounting_flag.is_some(),
+            rpc_client,
+            batched_call,
+            tx,
+            prev_metrics: Metrics::new(start),
+            start,
+            header,
+            recorder: None,
+            control: Arc::default(),
+            stop,
+            unavailable_items,
+            watches,
+            cpu_times: BTreeMap::new(),
+            mailbox,
+        };
+        Ok((node, this))
+    }
+
+    async fn record(&mut self, value: &impl serde::Serialize) -> anyhow::Result<()> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write_json_line(value).await?;
+        }
+        Ok(())
+    }
+
+    async fn record_pending_markers(&mut self) -> anyhow::Result<()> {
+        let pending_markers = std::mem::take(
+            &mut *self
+                .control
+                .pending_markers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+        for (time, label) in pending_markers {
+            let marker = Marker {
+                timestamp: time.saturating_duration_since(self.start),
+                marker: label,
+            };
+            self.record(&marker).await?;
         }
         Ok(())
     }
 
     fn run(mut self) {
-        let interval = Duration::from_secs(self.args.polling_interval.get() as u64);
+        let interval = self.args.polling_interval();
         let mut next_time = Duration::from_secs(0);
         smol::block_on(async {
-            if let Err(e) = self.write_json_line(&self.header.clone()).await {
+            if let Err(e) = self.record(&self.header.clone()).await {
                 log::error!("faild to write record file: {e}");
                 return;
             }
 
             loop {
+                if self.control.polling_paused.load(Ordering::SeqCst) {
+                    if self.stop.load(Ordering::SeqCst) {
+                        log::debug!("the polling thread has been stopped");
+                        break;
+                    }
+                    std::thread::sleep(PAUSED_POLLING_CHECK_INTERVAL);
+
+                    // Don't catch up on the polls skipped while paused.
+                    next_time = self.start.elapsed();
+                    continue;
+                }
                 match self.poll_once().await {
                     Err(e) => {
                         log::error!("faild to poll metrics: {e}");
@@ -458,16 +1458,42 @@ impl MetricsPollerThread {
                     Ok(metrics) => {
                         let elapsed = metrics.timestamp;
 
-                        if let Err(e) = self.write_json_line(&metrics).await {
+                        if let Err(e) = self.record_pending_markers().await {
                             log::error!("faild to write record file: {e}");
                             break;
                         }
 
-                        if self.tx.send(metrics).is_err() {
+                        if self.control.recording_paused.load(Ordering::SeqCst) {
+                            log::debug!("recording is paused");
+                        } else if let Err(e) = self.record(&metrics).await {
+                            log::error!("faild to write record file: {e}");
+                            break;
+                        }
+
+                        if self.stop.load(Ordering::SeqCst) {
+                            log::debug!("the polling thread has been stopped");
+                            break;
+                        }
+
+                        if let Some(ws_server) = &self.control.ws_server {
+                            ws_server.broadcast(&self.header, &metrics);
+                        }
+
+                        if self
+                            .tx
+                            .send((self.node_index, self.generation, metrics))
+                            .is_err()
+                        {
                             log::debug!("the main thread has terminated");
                             break;
                         }
 
+                        if self.duration.is_some_and(|d| elapsed >= d) {
+                            log::debug!("the collection duration has been reached");
+                            self.control.finished.store(true, Ordering::SeqCst);
+                            break;
+                        }
+
                         next_time += interval;
                         if let Some(sleep_duration) = next_time.checked_sub(elapsed) {
                             std::thread::sleep(sleep_duration);
@@ -478,7 +1504,7 @@ impl MetricsPollerThread {
         })
     }
 
-    fn insert_msacc_metrics(&self, metrics: &mut Metrics, msacc_threads: &[MSAccThread]) {
+    fn insert_msacc_metrics(&mut self, metrics: &mut Metrics, msacc_threads: &[MSAccThread]) {
         let mut aggregated_per_type = BTreeMap::<_, ThreadTime>::new();
         let mut aggregated_per_state_per_type = BTreeMap::<_, BTreeMap<&str, u64>>::new();
         let mut aggregated_per_thread_per_type = BTreeMap::<_, BTreeMap<u64, ThreadTime>>::new();
@@ -506,14 +1532,37 @@ impl MetricsPollerThread {
                     .or_default() += *value;
             }
         }
+        let mut total_weighted_utilization = 0.0;
+        let mut total_scheduler_threads = 0;
         for (ty, time) in aggregated_per_type {
-            let root_name = format!("utilization.{ty}");
+            let root_name = format!("utilization.{}", msacc_metric_name(ty));
             metrics.insert(&root_name, MetricValue::utilization(time.utilization()));
+
+            // CPU time accumulated since the start in microseconds, whose rate is the number of busy threads.
+            // The time of each poll is estimated from the utilization of the threads, which is measured
+            // only over `--msacc-window` if specified.
+            let busy_threads = aggregated_per_thread_per_type[ty]
+                .values()
+                .map(|time| time.utilization() / 100.0)
+                .sum::<f64>();
+            let poll_interval = metrics
+                .timestamp
+                .saturating_sub(self.prev_metrics.timestamp);
+            let cpu_time_name = format!("{CPU_TIME_METRIC_PREFIX}{}", msacc_metric_name(ty));
+            let cpu_time = self.cpu_times.entry(cpu_time_name.clone()).or_default();
+            *cpu_time += (busy_threads * poll_interval.as_secs_f64() * 1_000_000.0).round() as u64;
+            metrics.insert(&cpu_time_name, MetricValue::counter(*cpu_time));
+
+            let threads = aggregated_per_thread_per_type[ty].len();
+
+            if SCHEDULER_THREAD_TYPES.contains(&ty.as_str()) {
+                total_weighted_utilization += time.utilization() * threads as f64;
+                total_scheduler_threads += threads;
+            }
             for (state, value) in &aggregated_per_state_per_type[ty] {
-                let u = *value as f64 / time.realtime as f64 * 100.0;
                 metrics.insert(
                     &format!("{root_name}.state.{state}"),
-                    MetricValue::utilization_with_parent(u, &root_name),
+                    MetricValue::utilization_with_parent(time.ratio_of(*value), &root_name),
                 );
             }
 
@@ -528,61 +1577,153 @@ impl MetricsPollerThread {
                     MetricValue::utilization_with_parent(time.utilization(), &root_name),
                 );
             }
+
+            let mut thread_utilizations = aggregated_per_thread_per_type[ty]
+                .values()
+                .map(|time| time.utilization())
+                .collect::<Vec<_>>();
+            thread_utilizations.sort_by(|a, b| a.total_cmp(b));
+            if let (Some(min), Some(max)) =
+                (thread_utilizations.first(), thread_utilizations.last())
+            {
+                let n = thread_utilizations.len();
+                let median = if n % 2 == 0 {
+                    (thread_utilizations[n / 2 - 1] + thread_utilizations[n / 2]) / 2.0
+                } else {
+                    thread_utilizations[n / 2]
+                };
+                for (stat, value) in [("min", *min), ("median", median), ("max", *max)] {
+                    metrics.insert(
+                        &format!("{root_name}.{stat}"),
+                        MetricValue::utilization_with_parent(value, &root_name),
+                    );
+                }
+            }
+        }
+        if total_scheduler_threads > 0 {
+            metrics.insert(
+                TOTAL_UTILIZATION_METRIC_NAME,
+                MetricValue::utilization(
+                    total_weighted_utilization / total_scheduler_threads as f64,
+                ),
+            );
         }
     }
 
-    async fn poll_once(&mut self) -> anyhow::Result<Metrics> {
-        let mut metrics = Metrics::new(self.start);
-
-        let msacc = self
-            .rpc_client
-            .get_statistics_microstate_accounting()
-            .await?;
-        self.insert_msacc_metrics(&mut metrics, &msacc);
-
-        let processes = self.rpc_client.get_system_info_u64("process_count").await?;
+    async fn insert_system_info_metrics(
+        &self,
+        metrics: &mut Metrics,
+        source: &MetricsSource<'_, C>,
+    ) -> anyhow::Result<()> {
+        let processes = source.get_system_info_u64("process_count").await?;
         metrics.insert("system_info.process_count", MetricValue::gauge(processes));
 
-        let ports = self.rpc_client.get_system_info_u64("port_count").await?;
+        let ports = source.get_system_info_u64("port_count").await?;
         metrics.insert("system_info.port_count", MetricValue::gauge(ports));
 
-        let atoms = self.rpc_client.get_system_info_u64("atom_count").await?;
-        metrics.insert("system_info.atom_count", MetricValue::gauge(atoms));
+        if self.is_available("atom_count") {
+            let atoms = source.get_system_info_u64("atom_count").await?;
+            metrics.insert("system_info.atom_count", MetricValue::gauge(atoms));
+        }
 
-        let ets_tables = self.rpc_client.get_system_info_u64("ets_count").await?;
-        metrics.insert("system_info.ets_count", MetricValue::gauge(ets_tables));
+        if self.is_available("ets_count") {
+            let ets_tables = source.get_system_info_u64("ets_count").await?;
+            metrics.insert("system_info.ets_count", MetricValue::gauge(ets_tables));
+        }
 
-        let context_switches = self
-            .rpc_client
-            .get_statistics_1st_u64("context_switches")
-            .await?;
+        // The limits are the children of the counts so that they are shown next to each other.
+        for kind in LIMITED_RESOURCES {
+            let parent = format!("system_info.{kind}_count");
+            let item = format!("{kind}_limit");
+            if !metrics.items.contains_key(&parent) || !self.is_available(&item) {
+                continue;
+            }
+            let limit = source.get_system_info_u64(&item).await?;
+            metrics.insert(
+                &format!("system_info.{item}"),
+                MetricValue::gauge_with_parent(limit, &parent),
+            );
+        }
+
+        let schedulers = source.get_system_info_u64("schedulers").await?;
+        metrics.insert("system_info.schedulers", MetricValue::gauge(schedulers));
+        for (item, name) in [
+            ("schedulers_online", "online"),
+            ("dirty_cpu_schedulers", "dirty_cpu"),
+            ("logical_processors", "logical_processors"),
+        ] {
+            // `logical_processors` is `unknown` if the runtime system cannot detect it.
+            match source.get_system_info_u64(item).await {
+                Ok(n) => metrics.insert(
+                    &format!("system_info.schedulers.{name}"),
+                    MetricValue::gauge_with_parent(n, "system_info.schedulers"),
+                ),
+                Err(e) => log::debug!("failed to get system_info({item}): {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_statistics_metrics(
+        &self,
+        metrics: &mut Metrics,
+        source: &MetricsSource<'_, C>,
+    ) -> anyhow::Result<()> {
+        let context_switches = source.get_statistics_1st_u64("context_switches").await?;
         metrics.insert(
             "statistics.context_switches",
             MetricValue::counter(context_switches),
         );
 
-        let exact_reductions = self
-            .rpc_client
-            .get_statistics_1st_u64("exact_reductions")
-            .await?;
+        let exact_reductions = source.get_statistics_1st_u64("exact_reductions").await?;
         metrics.insert(
-            "statistics.exact_reductions",
+            REDUCTIONS_METRIC_NAME,
             MetricValue::counter(exact_reductions),
         );
 
-        let garbage_collection = self
-            .rpc_client
-            .get_statistics_1st_u64("garbage_collection")
+        let garbage_collection = source
+            .get_statistics_u64_tuple("garbage_collection")
             .await?;
+        let [gc_count, words_reclaimed, ..] = garbage_collection[..] else {
+            anyhow::bail!(
+                "expected `{{Number_of_GCs, Words_Reclaimed, 0}}`, but got {garbage_collection:?}"
+            );
+        };
+        metrics.insert("statistics.gc.count", MetricValue::counter(gc_count));
         metrics.insert(
-            "statistics.garbage_collection",
-            MetricValue::counter(garbage_collection),
+            "statistics.gc.words_reclaimed",
+            MetricValue::counter_with_parent(words_reclaimed, "statistics.gc.count"),
         );
 
-        let runtime = self.rpc_client.get_statistics_1st_u64("runtime").await?;
+        let runtime = source.get_statistics_1st_u64("runtime").await?;
         metrics.insert("statistics.runtime", MetricValue::counter(runtime));
 
-        let (in_bytes, out_bytes) = self.rpc_client.get_statistics_io().await?;
+        let wall_clock = source.get_statistics_1st_u64("wall_clock").await?;
+        metrics.insert(UPTIME_METRIC_NAME, MetricValue::gauge(wall_clock));
+
+        // The `_all` variants include the dirty run queues.
+        if self.is_available("total_active_tasks_all") {
+            let active_tasks = source.get_statistics_u64("total_active_tasks_all").await?;
+            metrics.insert("statistics.active_tasks", MetricValue::gauge(active_tasks));
+        }
+        if self.is_available("total_run_queue_lengths_all") {
+            let run_queue_lengths = source
+                .get_statistics_u64("total_run_queue_lengths_all")
+                .await?;
+            metrics.insert(
+                "statistics.run_queue_lengths_total",
+                MetricValue::gauge(run_queue_lengths),
+            );
+        }
+        Ok(())
+    }
+
+    async fn insert_io_metrics(
+        &self,
+        metrics: &mut Metrics,
+        source: &MetricsSource<'_, C>,
+    ) -> anyhow::Result<()> {
+        let (in_bytes, out_bytes) = source.get_statistics_io().await?;
         metrics.insert(
             "statistics.io.total_bytes",
             MetricValue::counter(in_bytes + out_bytes),
@@ -595,42 +1736,195 @@ impl MetricsPollerThread {
             "statistics.io.output_bytes",
             MetricValue::counter_with_parent(out_bytes, "statistics.io.total_bytes"),
         );
+        Ok(())
+    }
 
-        let run_queue_lengths = self
-            .rpc_client
+    async fn insert_run_queue_metrics(
+        &self,
+        metrics: &mut Metrics,
+        source: &MetricsSource<'_, C>,
+    ) -> anyhow::Result<()> {
+        let run_queue_lengths = source
             .get_statistics_u64_list("run_queue_lengths_all")
             .await?;
         let run_queue_total = run_queue_lengths.iter().copied().sum();
-        metrics.insert("statistics.run_queue", MetricValue::gauge(run_queue_total));
+        metrics.insert(RUN_QUEUE_METRIC_NAME, MetricValue::gauge(run_queue_total));
 
         let width = run_queue_lengths.len() / 10 + 1;
         for (i, n) in run_queue_lengths.into_iter().enumerate() {
             metrics.insert(
-                &format!("statistics.run_queue.{:0width$}", i),
-                MetricValue::gauge_with_parent(n, "statistics.run_queue"),
+                &format!("{RUN_QUEUE_METRIC_NAME}.{:0width$}", i),
+                MetricValue::gauge_with_parent(n, RUN_QUEUE_METRIC_NAME),
             );
         }
 
-        let mut memory = self.rpc_client.get_memory().await?;
+        // The imbalance is the ratio of the longest normal run queue to the mean length of them.
+        let normal_run_queue_lengths = source.get_statistics_u64_list("run_queue_lengths").await?;
+        let normal_run_queue_total = source.get_statistics_u64("total_run_queue_lengths").await?;
+        let max_run_queue = normal_run_queue_lengths.iter().copied().max().unwrap_or(0);
+        let imbalance = if normal_run_queue_total == 0 {
+            0.0
+        } else {
+            let mean = normal_run_queue_total as f64 / normal_run_queue_lengths.len() as f64;
+            max_run_queue as f64 / mean
+        };
+        metrics.insert(
+            "statistics.run_queue.imbalance",
+            MetricValue::float_gauge_with_parent(imbalance, RUN_QUEUE_METRIC_NAME),
+        );
+        Ok(())
+    }
+
+    async fn insert_memory_metrics(
+        &self,
+        metrics: &mut Metrics,
+        source: &MetricsSource<'_, C>,
+    ) -> anyhow::Result<()> {
+        let mut memory = source.get_memory().await?;
         metrics.insert(
-            "memory.total_bytes",
+            TOTAL_MEMORY_METRIC_NAME,
             MetricValue::gauge(memory.remove("total").expect("unreachable")),
         );
         for (k, v) in memory {
             metrics.insert(
                 &format!("memory.{k}_bytes"),
-                MetricValue::gauge_with_parent(v, "memory.total_bytes"),
+                MetricValue::gauge_with_parent(v, TOTAL_MEMORY_METRIC_NAME),
             );
         }
+        Ok(())
+    }
 
-        self.rpc_client
-            .set_system_flag_bool("microstate_accounting", "reset")
-            .await?;
+    /// Collects metric values by a batched RPC.
+    ///
+    /// If the batched RPC fails, `None` is returned and individual RPCs are used from then on.
+    async fn get_batched_values(&mut self) -> Option<BatchedValues> {
+        let call = self.batched_call.as_ref()?;
+        match self.rpc_client.get_all_metrics_batched(call).await {
+            Ok(values) => Some(values),
+            Err(e) => {
+                log::warn!("batched RPC failed (falling back to individual RPCs): {e}");
+                self.batched_call = None;
+                None
+            }
+        }
+    }
+
+    /// Inserts the values of `--watch` expressions.
+    ///
+    /// Failed evaluations are logged and omitted so that a broken expression doesn't stop the polling.
+    async fn insert_watch_metrics(&self, metrics: &mut Metrics) {
+        for (watch, exprs) in &self.watches {
+            match self.rpc_client.eval_number(exprs).await {
+                Ok(Number::Integer(value)) => {
+                    metrics.insert(&watch.metric_name(), MetricValue::signed_gauge(value))
+                }
+                Ok(Number::Float(value)) => {
+                    metrics.insert(&watch.metric_name(), MetricValue::float_gauge(value))
+                }
+                Err(e) => log::warn!(
+                    "failed to evaluate `--watch` expression {:?}: {e}",
+                    watch.label
+                ),
+            }
+        }
+    }
 
+    /// Inserts `statistics.total_message_queue_len`, which is sampled only once per `--mailbox-metric` interval.
+    async fn insert_mailbox_metric(&mut self, metrics: &mut Metrics) {
+        let Some(mailbox) = &mut self.mailbox else {
+            return;
+        };
+        if mailbox
+            .last
+            .is_none_or(|(time, _)| time.elapsed() >= mailbox.interval)
+        {
+            match self.rpc_client.eval_i64(&mailbox.exprs).await {
+                Ok(value) => mailbox.last = Some((Instant::now(), value.max(0) as u64)),
+                Err(e) => log::warn!("failed to get the total message queue length: {e}"),
+            }
+        }
+        if let Some((_, value)) = mailbox.last {
+            metrics.insert(
+                TOTAL_MESSAGE_QUEUE_LEN_METRIC_NAME,
+                MetricValue::gauge(value),
+            );
+        }
+    }
+
+    /// Returns `false` if the given `system_info/1` or `statistics/1` item is not supported by the OTP release of the node.
+    fn is_available(&self, item: &str) -> bool {
+        !self.unavailable_items.contains(&item)
+    }
+
+    async fn poll_once(&mut self) -> anyhow::Result<Metrics> {
+        let mut metrics = Metrics::new(self.start);
+        let poll_start = Instant::now();
+
+        if let Some(window) = self.args.msacc_window.filter(|_| self.msacc_enabled) {
+            // Measure the utilization over exactly `window` instead of the whole polling interval.
+            self.rpc_client
+                .set_system_flag_bool("microstate_accounting", "reset")
+                .await?;
+            smol::Timer::after(window).await;
+        }
+        if self.msacc_enabled {
+            let msacc = self
+                .rpc_client
+                .get_statistics_microstate_accounting()
+                .await?;
+            self.insert_msacc_metrics(&mut metrics, &msacc);
+        }
+
+        let source = match self.get_batched_values().await {
+            Some(values) => MetricsSource::Batched(values),
+            None => MetricsSource::Rpc(&self.rpc_client),
+        };
+        if self.args.is_metric_group_enabled(MetricGroup::SystemInfo) {
+            self.insert_system_info_metrics(&mut metrics, &source)
+                .await?;
+        }
+        if self.args.is_metric_group_enabled(MetricGroup::Statistics) {
+            self.insert_statistics_metrics(&mut metrics, &source)
+                .await?;
+        }
+        if self.args.is_metric_group_enabled(MetricGroup::Io) {
+            self.insert_io_metrics(&mut metrics, &source).await?;
+        }
+        if self.args.is_metric_group_enabled(MetricGroup::RunQueue)
+            && self.is_available("run_queue_lengths_all")
+        {
+            self.insert_run_queue_metrics(&mut metrics, &source).await?;
+        }
+        if self.args.is_metric_group_enabled(MetricGroup::Memory) {
+            self.insert_memory_metrics(&mut metrics, &source).await?;
+        }
+
+        self.insert_watch_metrics(&mut metrics).await;
+        self.insert_mailbox_metric(&mut metrics).await;
+
+        if self.msacc_enabled && self.args.msacc_window.is_none() {
+            self.rpc_client
+                .set_system_flag_bool("microstate_accounting", "reset")
+                .await?;
+        }
+
+        // The `--msacc-window` wait is not a part of the time spent on the RPCs.
+        let poll_duration = poll_start.elapsed().saturating_sub(
+            self.args
+                .msacc_window
+                .filter(|_| self.msacc_enabled)
+                .unwrap_or_default(),
+        );
         log::debug!(
-            "MetricsPoller::poll_once(): elapsed={:?}",
+            "MetricsPoller::poll_once(): elapsed={:?}, duration={poll_duration:?}",
             metrics.timestamp
         );
+        if self.args.self_metrics {
+            metrics.insert(
+                POLL_DURATION_METRIC_NAME,
+                MetricValue::gauge(poll_duration.as_millis() as u64),
+            );
+        }
         metrics.calc_delta(&self.prev_metrics);
 
         self.prev_metrics = metrics.clone();
@@ -639,6 +1933,22 @@ impl MetricsPollerThread {
     }
 }
 
+/// Maps a msacc thread type to the name used in `utilization.*` metrics.
+///
+/// Which thread types are reported depends on the target node:
+/// - `dirty_cpu_scheduler` and `dirty_io_scheduler`: OTP 20 or later (dirty schedulers are always enabled since OTP 21)
+/// - `poll`: OTP 21 or later
+/// - `async`: only when the async thread pool is enabled (`+A` > 0)
+///
+/// Thread types that are not reported by the node are simply omitted from the metrics.
+fn msacc_metric_name(thread_type: &str) -> &str {
+    match thread_type {
+        "dirty_cpu_scheduler" => "dirty_cpu",
+        "dirty_io_scheduler" => "dirty_io",
+        ty => ty,
+    }
+}
+
 #[derive(Debug, Default)]
 struct ThreadTime {
     runtime: u64,
@@ -647,6 +1957,628 @@ struct ThreadTime {
 
 impl ThreadTime {
     fn utilization(&self) -> f64 {
-        self.runtime as f64 / self.realtime as f64 * 100.0
+        self.ratio_of(self.runtime)
+    }
+
+    /// Returns `time` in percent of the real time.
+    fn ratio_of(&self, time: u64) -> f64 {
+        // A thread that has just been started (or an empty msacc window) has no elapsed time.
+        if self.realtime == 0 {
+            return 0.0;
+        }
+        time as f64 / self.realtime as f64 * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use erl_dist::term::{Atom, FixInteger, List, Map, Term, Tuple};
+
+    fn collect_args(args: &[&str]) -> CollectArgs {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            collect: CollectArgs,
+        }
+        let args = ["erldash", "foo@localhost"].iter().chain(args);
+        <Cli as clap::Parser>::parse_from(args).collect
+    }
+
+    fn run_args(args: &[&str]) -> RunArgs {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[clap(flatten)]
+            run: RunArgs,
+        }
+        let args = ["erldash"].iter().chain(args);
+        <Cli as clap::Parser>::parse_from(args).run
+    }
+
+    /// State of a node answered by [`MockRpc`], shared by all the connections to the node.
+    #[derive(Debug, Default)]
+    struct MockNode {
+        msacc: bool,
+
+        // Calls made to the node, such as `erlang:statistics(io)`.
+        calls: Vec<String>,
+    }
+
+    static MOCK_NODES: Mutex<BTreeMap<String, MockNode>> = Mutex::new(BTreeMap::new());
+
+    fn with_mock_node<T>(node_name: &str, f: impl FnOnce(&mut MockNode) -> T) -> T {
+        let mut nodes = MOCK_NODES.lock().unwrap_or_else(|e| e.into_inner());
+        f(nodes.entry(node_name.to_owned()).or_default())
+    }
+
+    fn mock_calls(node_name: &str) -> Vec<String> {
+        with_mock_node(node_name, |node| node.calls.clone())
+    }
+
+    /// [`Rpc`] that answers the calls made by erldash with made-up values instead of a live node.
+    ///
+    /// The behavior depends on the name of the node:
+    /// - `otpNN@...` reports OTP NN as its release
+    /// - `nobatch@...` can't parse expressions, so the batched RPC is not available
+    ///
+    /// Each test uses its own node names as the node states are shared by the tests.
+    #[derive(Debug, Clone)]
+    struct MockRpc {
+        node_name: String,
+    }
+
+    impl MockRpc {
+        fn name(&self) -> &str {
+            self.node_name.split('@').next().unwrap_or_default()
+        }
+
+        /// Returns the value of `erlang:statistics(Item)` at the `n`-th call for the item.
+        fn statistics(&self, item: &str, n: i32) -> Term {
+            let int = |v: i32| Term::from(FixInteger::from(v));
+            let tuple = |elements: Vec<Term>| Term::from(Tuple::from(elements));
+            let key_value = |key: &str, value: Term| tuple(vec![Atom::from(key).into(), value]);
+            match item {
+                "context_switches" => tuple(vec![int(n * 100), int(0)]),
+                "exact_reductions" => tuple(vec![int(n * 1000), int(1000)]),
+                "garbage_collection" => tuple(vec![int(n * 10), int(n * 500), int(0)]),
+                "runtime" | "wall_clock" => tuple(vec![int(n * 1000), int(1000)]),
+                "io" => tuple(vec![
+                    key_value("input", int(n * 2000)),
+                    key_value("output", int(n * 3000)),
+                ]),
+                // Two normal run queues and a dirty one.
+                "run_queue_lengths_all" if self.name() == "idle" => {
+                    List::from(vec![int(0), int(0), int(0)]).into()
+                }
+                "run_queue_lengths" if self.name() == "idle" => {
+                    List::from(vec![int(0), int(0)]).into()
+                }
+                "total_run_queue_lengths" | "total_run_queue_lengths_all"
+                    if self.name() == "idle" =>
+                {
+                    int(0)
+                }
+                "run_queue_lengths_all" => List::from(vec![int(1), int(2), int(0)]).into(),
+                "run_queue_lengths" => List::from(vec![int(1), int(2)]).into(),
+                "total_run_queue_lengths" | "total_run_queue_lengths_all" => int(3),
+                "total_active_tasks_all" => int(5),
+                "microstate_accounting" => {
+                    let map = |entries: Vec<(&str, Term)>| {
+                        Term::from(Map::from(
+                            entries
+                                .into_iter()
+                                .map(|(k, v)| (Atom::from(k).into(), v))
+                                .collect::<Vec<_>>(),
+                        ))
+                    };
+                    let thread = |id: i32, ty: &str, counters: Vec<(&str, i32)>| {
+                        let counters = counters.into_iter().map(|(k, v)| (k, int(v))).collect();
+                        map(vec![
+                            ("id", int(id)),
+                            ("type", Atom::from(ty).into()),
+                            ("counters", map(counters)),
+                        ])
+                    };
+                    List::from(vec![
+                        thread(1, "scheduler", vec![("sleep", 750), ("emulator", 250)]),
+                        thread(
+                            2,
+                            "scheduler",
+                            vec![("sleep", 250), ("emulator", 500), ("gc", 250)],
+                        ),
+                        thread(1, "dirty_cpu_scheduler", vec![("sleep", 1000)]),
+                        thread(1, "aux", vec![("sleep", 500), ("aux", 500)]),
+                    ])
+                    .into()
+                }
+                _ => panic!("unexpected statistics item: {item}"),
+            }
+        }
+
+        fn system_info(&self, item: &str) -> Term {
+            let value = match item {
+                "system_version" => {
+                    let release = self.name().strip_prefix("otp").unwrap_or("26");
+                    let version = format!("Erlang/OTP {release} [erts-14.0]\n");
+                    return List::from(
+                        version
+                            .bytes()
+                            .map(|b| FixInteger::from(i32::from(b)).into())
+                            .collect::<Vec<Term>>(),
+                    )
+                    .into();
+                }
+                "process_count" => 100,
+                "port_count" => 10,
+                "atom_count" => 5000,
+                "ets_count" => 20,
+                "process_limit" | "port_limit" | "atom_limit" | "ets_limit" => 262144,
+                "schedulers" | "schedulers_online" | "dirty_cpu_schedulers" => 4,
+                "logical_processors" => 8,
+                _ if crate::erlang::NODE_INFO_ITEMS
+                    .iter()
+                    .any(|(_, name)| *name == item) =>
+                {
+                    return Atom::from("mock").into();
+                }
+                _ => panic!("unexpected system_info item: {item}"),
+            };
+            FixInteger::from(value).into()
+        }
+
+        fn memory(&self) -> Term {
+            let items = [("total", 1000), ("processes", 600), ("binary", 400)];
+            List::from(
+                items
+                    .into_iter()
+                    .map(|(k, v)| {
+                        Tuple::from(vec![Atom::from(k).into(), FixInteger::from(v).into()]).into()
+                    })
+                    .collect::<Vec<Term>>(),
+            )
+            .into()
+        }
+
+        /// Evaluates the expressions built by erldash, whose tokens are the source itself (see `call()`).
+        fn eval(&self, source: &str, n: i32) -> Term {
+            let value = if source.starts_with("{[{I, erlang:statistics(I)}") {
+                let items = |prefix: &str| {
+                    let rest = &source[source.find(prefix).expect("unreachable") + prefix.len()..];
+                    rest[..rest.find(']').expect("unreachable")]
+                        .split(", ")
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                };
+                let pairs = |items: Vec<String>, value: &dyn Fn(&str) -> Term| {
+                    List::from(
+                        items
+                            .iter()
+                            .map(|item| {
+                                Tuple::from(vec![Atom::from(item.as_str()).into(), value(item)])
+                                    .into()
+                            })
+                            .collect::<Vec<Term>>(),
+                    )
+                };
+                let statistics = pairs(items("statistics(I)} || I <- ["), &|item| {
+                    self.statistics(item, n)
+                });
+                let system_info = pairs(items("system_info(I)} || I <- ["), &|item| {
+                    self.system_info(item)
+                });
+                let memory = if source.contains("erlang:memory()") {
+                    self.memory()
+                } else {
+                    List::nil().into()
+                };
+                Tuple::from(vec![statistics.into(), system_info.into(), memory]).into()
+            } else if source == TOTAL_MESSAGE_QUEUE_LEN_EXPR {
+                FixInteger::from(7).into()
+            } else if let Ok(v) = source.trim_end_matches('.').parse::<i32>() {
+                FixInteger::from(v).into()
+            } else {
+                panic!("unexpected expression: {source}");
+            };
+            Tuple::from(vec![Atom::from("value").into(), value, List::nil().into()]).into()
+        }
+    }
+
+    impl Rpc for MockRpc {
+        async fn connect(
+            erlang_node: &NodeName,
+            _name_type: crate::erlang::NameType,
+            _port: Option<u16>,
+            _transport: &crate::erlang::Transport,
+            _cookie: &str,
+        ) -> crate::erlang::ErlangResult<Self> {
+            Ok(Self {
+                node_name: erlang_node.to_string(),
+            })
+        }
+
+        fn port(&self) -> u16 {
+            4369
+        }
+
+        async fn call(
+            &self,
+            module: &str,
+            function: &str,
+            args: List,
+        ) -> crate::erlang::ErlangResult<Term> {
+            let atom = |i: usize| match &args.elements[i] {
+                Term::Atom(atom) => atom.name.clone(),
+                term => panic!("expected an atom: {term}"),
+            };
+            let string = |i: usize| match &args.elements[i] {
+                Term::List(list) => list
+                    .elements
+                    .iter()
+                    .map(|term| match term {
+                        Term::FixInteger(c) => char::from(c.value as u8),
+                        term => panic!("expected a character: {term}"),
+                    })
+                    .collect::<String>(),
+                term => panic!("expected a string: {term}"),
+            };
+            let ok = |value: Term| Term::from(Tuple::from(vec![Atom::from("ok").into(), value]));
+            let call = match (module, function) {
+                ("erlang", "memory") => format!("{module}:{function}()"),
+                ("erlang", _) => format!("{module}:{function}({})", atom(0)),
+                ("erl_scan" | "erl_parse", _) => format!("{module}:{function}"),
+                _ => format!("{module}:{function}({})", string(0)),
+            };
+            let n = with_mock_node(&self.node_name, |node| {
+                node.calls.push(call.clone());
+                node.calls.iter().filter(|c| **c == call).count() as i32
+            });
+
+            let term = match (module, function) {
+                ("erlang", "system_info") => self.system_info(&atom(0)),
+                ("erlang", "statistics") => self.statistics(&atom(0), n),
+                ("erlang", "memory") => self.memory(),
+                ("erlang", "system_flag") => {
+                    let old = with_mock_node(&self.node_name, |node| {
+                        let old = node.msacc;
+                        match atom(1).as_str() {
+                            "true" => node.msacc = true,
+                            "false" => node.msacc = false,
+                            _ => {}
+                        }
+                        old
+                    });
+                    Atom::from(if old { "true" } else { "false" }).into()
+                }
+                // The source itself is used as the tokens and the parsed expressions.
+                ("erl_scan", "string") if self.name() == "nobatch" => {
+                    Tuple::from(vec![Atom::from("error").into(), Atom::from("mock").into()]).into()
+                }
+                ("erl_scan", "string") | ("erl_parse", "parse_exprs") => {
+                    ok(args.elements[0].clone())
+                }
+                ("erl_eval", "exprs") => self.eval(&string(0), n),
+                _ => panic!("unexpected call: {module}:{function}"),
+            };
+            Ok(term)
+        }
+    }
+
+    fn connect_mock(
+        node_name: &str,
+        args: &[&str],
+    ) -> (RealtimeNode<MockRpc>, MetricsPollerThread<MockRpc>) {
+        let args = collect_args(&[&["--cookie", "foo"], args].concat());
+        let (tx, _rx) = mpsc::channel();
+        MetricsPollerThread::<MockRpc>::connect(args, node_name.parse().unwrap(), 0, tx).unwrap()
+    }
+
+    #[test]
+    fn collect_once_works_without_a_live_node() {
+        let mut args = collect_args(&["--cookie", "foo"]);
+        args.erlang_nodes = vec!["collect@localhost".parse().unwrap()];
+        let metrics = collect_once::<MockRpc>(args).unwrap();
+
+        assert!(matches!(
+            metrics.items["system_info.process_count"],
+            MetricValue::Gauge { value: 100, .. }
+        ));
+        assert!(matches!(
+            metrics.items["statistics.io.input_bytes"],
+            MetricValue::Counter {
+                raw_value: 2000,
+                value: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            metrics.items[TOTAL_MEMORY_METRIC_NAME],
+            MetricValue::Gauge { value: 1000, .. }
+        ));
+        assert!(matches!(
+            metrics.items["utilization.scheduler"],
+            MetricValue::Utilization { value, .. } if value == 50.0
+        ));
+
+        // The metrics are collected by the batched RPC, and microstate accounting is restored afterwards.
+        let calls = mock_calls("collect@localhost");
+        assert!(calls.iter().any(|call| call.starts_with("erl_eval:exprs(")));
+        assert!(!calls.contains(&"erlang:statistics(io)".to_owned()));
+        assert!(!with_mock_node("collect@localhost", |node| node.msacc));
+    }
+
+    #[test]
+    fn poll_once_falls_back_to_individual_rpcs() {
+        let (_batched_node, mut batched) = connect_mock("batch@localhost", &[]);
+        let (_node, mut individual) = connect_mock("nobatch@localhost", &[]);
+        assert!(batched.batched_call.is_some());
+        assert!(individual.batched_call.is_none());
+
+        for _ in 0..2 {
+            let expected = smol::block_on(batched.poll_once()).unwrap();
+            let actual = smol::block_on(individual.poll_once()).unwrap();
+            assert_eq!(
+                expected.items.keys().collect::<Vec<_>>(),
+                actual.items.keys().collect::<Vec<_>>()
+            );
+            for (name, value) in &expected.items {
+                assert_eq!(
+                    value.as_f64().is_some(),
+                    actual.items[name].as_f64().is_some(),
+                    "{name}"
+                );
+            }
+        }
+        assert!(mock_calls("nobatch@localhost").contains(&"erlang:statistics(io)".to_owned()));
+        assert!(!mock_calls("batch@localhost").contains(&"erlang:statistics(io)".to_owned()));
+    }
+
+    #[test]
+    fn poll_once_skips_filtered_and_unavailable_items() {
+        let (_node, mut thread) = connect_mock(
+            "otp19@localhost",
+            &["--include", "io", "--include", "run_queue"],
+        );
+        let metrics = smol::block_on(thread.poll_once()).unwrap();
+        assert!(metrics.items.contains_key("statistics.io.input_bytes"));
+        assert!(!metrics.items.contains_key("system_info.process_count"));
+        assert!(!metrics.items.contains_key(TOTAL_MEMORY_METRIC_NAME));
+        assert!(!metrics.items.contains_key(RUN_QUEUE_METRIC_NAME));
+
+        let calls = mock_calls("otp19@localhost");
+        let eval = calls
+            .iter()
+            .find(|call| call.starts_with("erl_eval:exprs("))
+            .unwrap();
+        assert!(eval.contains("I <- [io, run_queue_lengths, total_run_queue_lengths]"));
+        assert!(eval.contains("system_info(I)} || I <- []"));
+        assert!(!eval.contains("erlang:memory()"));
+        assert!(!calls
+            .iter()
+            .any(|call| call.starts_with("erlang:system_flag")));
+    }
+
+    #[test]
+    fn poll_once_computes_counter_rates() {
+        let (_node, mut thread) = connect_mock("rates@localhost", &["--watch", "answer=42"]);
+        let first = smol::block_on(thread.poll_once()).unwrap();
+        assert!(matches!(
+            first.items["statistics.io.output_bytes"],
+            MetricValue::Counter { value: None, .. }
+        ));
+        assert!(matches!(
+            first.items["custom.watch.answer"],
+            MetricValue::Gauge { value: 42, .. }
+        ));
+
+        std::thread::sleep(Duration::from_millis(10));
+        let second = smol::block_on(thread.poll_once()).unwrap();
+        let MetricValue::Counter {
+            raw_value,
+            value: Some(rate),
+            ..
+        } = second.items["statistics.io.output_bytes"]
+        else {
+            panic!(
+                "unexpected value: {:?}",
+                second.items["statistics.io.output_bytes"]
+            );
+        };
+        assert_eq!(raw_value, 6000);
+        let elapsed = (second.timestamp - first.timestamp).as_secs_f64();
+        assert!((rate - 3000.0 / elapsed).abs() < 1e-6 * rate);
+    }
+
+    fn gauge_value(metrics: &Metrics, name: &str) -> f64 {
+        match &metrics.items[name] {
+            MetricValue::Gauge { value, .. } => *value as f64,
+            MetricValue::FloatGauge { value, .. } | MetricValue::Utilization { value, .. } => {
+                *value
+            }
+            value => panic!("{name} is not a gauge: {value:?}"),
+        }
+    }
+
+    #[test]
+    fn poll_once_parses_run_queues() {
+        let (_node, mut thread) = connect_mock("runqueue@localhost", &["--include", "run_queue"]);
+        let metrics = smol::block_on(thread.poll_once()).unwrap();
+        assert_eq!(gauge_value(&metrics, RUN_QUEUE_METRIC_NAME), 3.0);
+        for (name, value) in [("0", 1.0), ("1", 2.0), ("2", 0.0)] {
+            let name = format!("{RUN_QUEUE_METRIC_NAME}.{name}");
+            assert_eq!(gauge_value(&metrics, &name), value);
+            assert_eq!(
+                metrics.items[&name].parent(),
+                Some(RUN_QUEUE_METRIC_NAME),
+                "{name}"
+            );
+        }
+        // The longest normal run queue (2) over their mean length (1.5).
+        let imbalance = gauge_value(&metrics, "statistics.run_queue.imbalance");
+        assert!((imbalance - 2.0 / 1.5).abs() < 1e-9, "{imbalance}");
+
+        let (_node, mut thread) = connect_mock("idle@localhost", &["--include", "run_queue"]);
+        let metrics = smol::block_on(thread.poll_once()).unwrap();
+        assert_eq!(gauge_value(&metrics, RUN_QUEUE_METRIC_NAME), 0.0);
+        assert_eq!(gauge_value(&metrics, "statistics.run_queue.0"), 0.0);
+        assert_eq!(gauge_value(&metrics, "statistics.run_queue.imbalance"), 0.0);
+    }
+
+    #[test]
+    fn poll_once_aggregates_msacc() {
+        let (_node, mut thread) = connect_mock("msacc@localhost", &["--include", "msacc"]);
+        let metrics = smol::block_on(thread.poll_once()).unwrap();
+        let cases = [
+            // (750 + 250) of 2000 microseconds are not spent sleeping.
+            ("utilization.scheduler", 50.0),
+            ("utilization.scheduler.state.emulator", 37.5),
+            ("utilization.scheduler.state.gc", 12.5),
+            ("utilization.scheduler.state.sleep", 50.0),
+            ("utilization.scheduler.thread.1", 25.0),
+            ("utilization.scheduler.thread.2", 75.0),
+            ("utilization.scheduler.min", 25.0),
+            ("utilization.scheduler.median", 50.0),
+            ("utilization.scheduler.max", 75.0),
+            ("utilization.dirty_cpu", 0.0),
+            ("utilization.aux", 50.0),
+            ("utilization.aux.state.aux", 50.0),
+            // The average over the 3 scheduler threads, excluding the aux thread.
+            (TOTAL_UTILIZATION_METRIC_NAME, 100.0 / 3.0),
+        ];
+        for (name, expected) in cases {
+            let actual = gauge_value(&metrics, name);
+            assert!((actual - expected).abs() < 1e-9, "{name}: {actual}");
+        }
+        assert_eq!(
+            metrics.items["utilization.scheduler.thread.1"].parent(),
+            Some("utilization.scheduler")
+        );
+        assert!(!metrics.items.contains_key("utilization.dirty_io"));
+    }
+
+    #[test]
+    fn switch_node_keeps_msacc_enabled_on_the_same_node() {
+        let mut poller = RealtimeMetricsPoller::<MockRpc>::start_thread(run_args(&[
+            "switch@localhost",
+            "--cookie",
+            "foo",
+        ]))
+        .unwrap();
+        let (node_index, generation, _) = poller.rx.recv().unwrap();
+        assert_eq!((node_index, generation), (0, 0));
+        assert_eq!(poller.nodes[0].old_microstate_accounting_flag, Some(false));
+
+        let header = poller
+            .switch_node(0, "switch@localhost".parse().unwrap())
+            .unwrap();
+        assert_eq!(header.node_name, "switch@localhost");
+        assert_eq!(poller.nodes[0].generation, 1);
+
+        // The new connection restores the original flag instead of the old one.
+        assert_eq!(poller.nodes[0].old_microstate_accounting_flag, Some(false));
+        assert!(with_mock_node("switch@localhost", |node| node.msacc));
+        let (_, generation, _) = std::iter::from_fn(|| poller.rx.recv().ok())
+            .find(|(_, generation, _)| *generation == 1)
+            .unwrap();
+        assert_eq!(generation, 1);
+
+        drop(poller);
+        assert!(!with_mock_node("switch@localhost", |node| node.msacc));
+    }
+
+    #[test]
+    fn batched_items_follow_metric_groups() {
+        let items = batched_items(&collect_args(&[]), &[]);
+        assert!(items.statistics.contains(&"io"));
+        assert!(items.system_info.contains(&"ets_count"));
+        assert!(items.memory);
+
+        let items = batched_items(&collect_args(&["--include", "io"]), &[]);
+        assert_eq!(items.statistics, ["io"]);
+        assert!(items.system_info.is_empty());
+        assert!(!items.memory);
+
+        let items = batched_items(
+            &collect_args(&["--exclude", "memory", "--exclude", "system_info"]),
+            &["run_queue_lengths_all"],
+        );
+        assert!(!items.statistics.contains(&"run_queue_lengths_all"));
+        assert!(items.statistics.contains(&"run_queue_lengths"));
+        assert!(items.system_info.is_empty());
+        assert!(!items.memory);
+
+        let items = batched_items(&collect_args(&["--include", "msacc"]), &[]);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn format_u64_works() {
+        let cases = [
+            (0, ["0", "0", "0", "0"]),
+            (999, ["999", "999", "999", "999"]),
+            (1000, ["1,000", "1.000", "1 000", "1000"]),
+            (1234567, ["1,234,567", "1.234.567", "1 234 567", "1234567"]),
+            (
+                u64::MAX,
+                [
+                    "18,446,744,073,709,551,615",
+                    "18.446.744.073.709.551.615",
+                    "18 446 744 073 709 551 615",
+                    "18446744073709551615",
+                ],
+            ),
+        ];
+        let formats = [
+            NumberFormat::Comma,
+            NumberFormat::Dot,
+            NumberFormat::Space,
+            NumberFormat::None,
+        ];
+        for (n, expected) in cases {
+            for (format, expected) in formats.into_iter().zip(expected) {
+                assert_eq!(format_u64_with(format, n, ""), expected, "{format:?}");
+            }
+        }
+        assert_eq!(format_u64_with(NumberFormat::Comma, 1000, " B"), "1,000 B");
+    }
+
+    #[test]
+    fn cpu_time_is_shown_in_seconds() {
+        let rate = MetricValue::Counter {
+            raw_value: 3_000_000,
+            value: Some(1_600_000.0),
+            parent: None,
+        };
+        assert_eq!(rate.format("cpu_time.scheduler"), "1.60 s/s");
+        assert_eq!(rate.format("statistics.reductions"), "1,600,000/s");
+        assert_eq!(format_micros_as_secs(3_000_000.0, ""), "3.00 s");
+    }
+
+    #[test]
+    fn format_i64_works() {
+        // The tests don't call `set_number_format()`, so the default format (comma) is used.
+        assert_eq!(format_i64(0, ""), "0");
+        assert_eq!(format_i64(1000, ""), "1,000");
+        assert_eq!(format_i64(-1, ""), "-1");
+        assert_eq!(format_i64(-1000, ""), "-1,000");
+        assert_eq!(format_i64(-1000, " B"), "-1,000 B");
+        assert_eq!(format_i64(i64::MIN, ""), "-9,223,372,036,854,775,808");
+        assert_eq!(format_i64(i64::MAX, ""), "9,223,372,036,854,775,807");
+    }
+
+    #[test]
+    fn thread_time_utilization_works() {
+        let time = ThreadTime {
+            runtime: 250,
+            realtime: 1000,
+        };
+        assert_eq!(time.utilization(), 25.0);
+        assert_eq!(time.ratio_of(500), 50.0);
+
+        // No real time has elapsed.
+        let time = ThreadTime::default();
+        assert_eq!(time.utilization(), 0.0);
+        assert_eq!(time.ratio_of(0), 0.0);
+        assert_eq!(time.ratio_of(10), 0.0);
     }
 }
@@ -0,0 +1,268 @@
+//! Human-readable descriptions of the metrics collected by erldash.
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Description of a metric.
+#[derive(Debug, Clone, Copy)]
+pub struct Description {
+    /// One-paragraph explanation of the metric.
+    pub text: &'static str,
+
+    /// Erlang expression from which the metric value is obtained.
+    pub source: &'static str,
+}
+
+/// Returns the description of the given metric if available.
+pub fn get(metric_name: &str) -> Option<&'static Description> {
+    DESCRIPTIONS.get(metric_name)
+}
+
+static DESCRIPTIONS: LazyLock<BTreeMap<&'static str, Description>> = LazyLock::new(|| {
+    [
+        (
+            "system_info.process_count",
+            "Number of processes currently existing at the node.",
+            "erlang:system_info(process_count)",
+        ),
+        (
+            "system_info.port_count",
+            "Number of ports currently existing at the node.",
+            "erlang:system_info(port_count)",
+        ),
+        (
+            "system_info.atom_count",
+            "Number of atoms currently existing at the node. \
+             Atoms are never garbage collected, so a steady increase indicates an atom leak.",
+            "erlang:system_info(atom_count)",
+        ),
+        (
+            "system_info.ets_count",
+            "Number of ETS tables currently existing at the node.",
+            "erlang:system_info(ets_count)",
+        ),
+        (
+            "system_info.process_limit",
+            "Maximum number of processes that can exist at the node simultaneously (`+P`).",
+            "erlang:system_info(process_limit)",
+        ),
+        (
+            "system_info.port_limit",
+            "Maximum number of ports that can exist at the node simultaneously (`+Q`).",
+            "erlang:system_info(port_limit)",
+        ),
+        (
+            "system_info.atom_limit",
+            "Maximum number of atoms allowed at the node (`+t`). The node crashes when the limit is reached.",
+            "erlang:system_info(atom_limit)",
+        ),
+        (
+            "system_info.ets_limit",
+            "Maximum number of ETS tables allowed at the node.",
+            "erlang:system_info(ets_limit)",
+        ),
+        (
+            "system_info.schedulers",
+            "Number of normal scheduler threads created by the node. \
+             Utilization values should be interpreted relative to these scheduler counts.",
+            "erlang:system_info(schedulers)",
+        ),
+        (
+            "system_info.schedulers.online",
+            "Number of normal scheduler threads that are currently online.",
+            "erlang:system_info(schedulers_online)",
+        ),
+        (
+            "system_info.schedulers.dirty_cpu",
+            "Number of dirty CPU scheduler threads.",
+            "erlang:system_info(dirty_cpu_schedulers)",
+        ),
+        (
+            "system_info.schedulers.logical_processors",
+            "Number of logical processors configured on the system.",
+            "erlang:system_info(logical_processors)",
+        ),
+        (
+            "statistics.context_switches",
+            "Number of context switches (process schedulings) per second.",
+            "erlang:statistics(context_switches)",
+        ),
+        (
+            "statistics.exact_reductions",
+            "Number of reductions executed per second. \
+             A reduction is roughly a function call, so this is a measure of the amount of work done by the node.",
+            "erlang:statistics(exact_reductions)",
+        ),
+        (
+            "statistics.gc.count",
+            "Number of garbage collections performed per second.",
+            "erlang:statistics(garbage_collection)",
+        ),
+        (
+            "statistics.gc.words_reclaimed",
+            "Number of words reclaimed by garbage collections per second. \
+             A high value indicates GC pressure caused by processes allocating lots of short-lived data.",
+            "erlang:statistics(garbage_collection)",
+        ),
+        (
+            "statistics.runtime",
+            "CPU time (in milliseconds) consumed by the Erlang runtime system per second, summed over all threads.",
+            "erlang:statistics(runtime)",
+        ),
+        (
+            "statistics.wall_clock_ms",
+            "Time (in milliseconds) elapsed since the node was started.",
+            "erlang:statistics(wall_clock)",
+        ),
+        (
+            "statistics.io.total_bytes",
+            "Total number of bytes received and sent through ports per second.",
+            "erlang:statistics(io)",
+        ),
+        (
+            "statistics.io.input_bytes",
+            "Number of bytes received through ports per second.",
+            "erlang:statistics(io)",
+        ),
+        (
+            "statistics.io.output_bytes",
+            "Number of bytes sent through ports per second.",
+            "erlang:statistics(io)",
+        ),
+        (
+            "statistics.active_tasks",
+            "Number of processes and ports that are ready to run or currently running, \
+             including those on the dirty schedulers.",
+            "erlang:statistics(total_active_tasks_all)",
+        ),
+        (
+            "statistics.run_queue_lengths_total",
+            "Number of processes and ports that are ready to run but not running, \
+             including those in the dirty run queues.",
+            "erlang:statistics(total_run_queue_lengths_all)",
+        ),
+        (
+            "statistics.run_queue",
+            "Total number of processes and ports that are ready to run. \
+             A persistently large value means the schedulers cannot keep up with the load.",
+            "erlang:statistics(run_queue_lengths_all)",
+        ),
+        (
+            "statistics.run_queue.imbalance",
+            "Ratio of the longest normal run queue to the average normal run queue length. \
+             Values much larger than 1 mean the load is unevenly distributed among schedulers.",
+            "erlang:statistics(run_queue_lengths), erlang:statistics(total_run_queue_lengths)",
+        ),
+        (
+            "statistics.total_message_queue_len",
+            "Total number of messages waiting in the mailboxes of all processes (`--mailbox-metric`). \
+             Sudden growth means that some processes cannot keep up with the incoming messages.",
+            "erlang:process_info(P, message_queue_len) for every P in erlang:processes()",
+        ),
+        (
+            "erldash.poll_duration_ms",
+            "Time taken by erldash to collect the metrics of a poll (`--self-metrics`). \
+             Large values mean a slow network link or a node that is too busy to respond to RPCs promptly.",
+            "measured by erldash (excluding the `--msacc-window` wait)",
+        ),
+        (
+            "memory.total_bytes",
+            "Total amount of memory currently allocated by the node.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.processes_bytes",
+            "Amount of memory currently allocated for processes.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.processes_used_bytes",
+            "Amount of memory currently used by processes.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.system_bytes",
+            "Amount of memory currently allocated for the runtime system that is not directly related to processes.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.atom_bytes",
+            "Amount of memory currently allocated for atoms.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.atom_used_bytes",
+            "Amount of memory currently used for atoms.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.binary_bytes",
+            "Amount of memory currently allocated for off-heap (reference counted) binaries.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.code_bytes",
+            "Amount of memory currently allocated for Erlang code.",
+            "erlang:memory()",
+        ),
+        (
+            "memory.ets_bytes",
+            "Amount of memory currently allocated for ETS tables.",
+            "erlang:memory()",
+        ),
+        (
+            "utilization.total",
+            "Utilization of all scheduler threads (normal, dirty CPU and dirty IO), weighted by the number of threads.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.scheduler",
+            "Utilization of the normal scheduler threads, which execute Erlang code.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.dirty_cpu",
+            "Utilization of the dirty CPU scheduler threads, which execute long-running CPU-bound NIFs and BIFs.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.dirty_io",
+            "Utilization of the dirty IO scheduler threads, which execute NIFs and BIFs doing blocking IO.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.aux",
+            "Utilization of the auxiliary threads, which handle tasks such as timers and memory deallocation.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.async",
+            "Utilization of the async thread pool (only used when the node is started with `+A` > 0).",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "utilization.poll",
+            "Utilization of the IO polling threads.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "cpu_time.scheduler",
+            "CPU time consumed by the normal scheduler threads. The rate is in CPU-seconds per second \
+             (e.g., 1.60 s/s means 1.6 CPUs are busy). Unlike utilization, this grows with the number of threads. \
+             Exported values are in microseconds.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "cpu_time.dirty_cpu",
+            "CPU time consumed by the dirty CPU scheduler threads. The rate is in CPU-seconds per second.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+        (
+            "cpu_time.dirty_io",
+            "Time consumed by the dirty IO scheduler threads (excluding sleep). The rate is in CPU-seconds per second.",
+            "erlang:statistics(microstate_accounting)",
+        ),
+    ]
+    .into_iter()
+    .map(|(name, text, source)| (name, Description { text, source }))
+    .collect()
+});
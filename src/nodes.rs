@@ -0,0 +1,64 @@
+use crate::NodesArgs;
+use erl_dist::epmd::{NodeEntry, NodeType};
+
+/// Prints the nodes registered in the EPMD on the given host.
+pub fn run(args: NodesArgs) -> anyhow::Result<()> {
+    let entries = smol::block_on(crate::erlang::list_epmd_nodes(&args.host))?;
+    if args.json {
+        let nodes = entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "name": format!("{}@{}", entry.name, args.host),
+                    "port": entry.port,
+                    "node_type": node_type(entry),
+                    "highest_version": entry.highest_version,
+                    "lowest_version": entry.lowest_version,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::Value::Array(nodes));
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        eprintln!("No Erlang nodes are registered in EPMD on {}", args.host);
+        return Ok(());
+    }
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            [
+                format!("{}@{}", entry.name, args.host),
+                entry.port.to_string(),
+                node_type(entry).to_owned(),
+                format!("{}-{}", entry.lowest_version, entry.highest_version),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["NAME", "PORT", "TYPE", "VERSIONS"].map(|title| title.to_owned());
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = std::cmp::max(*width, cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+fn node_type(entry: &NodeEntry) -> &'static str {
+    match entry.node_type {
+        NodeType::Normal => "normal",
+        NodeType::Hidden => "hidden",
+        _ => "other",
+    }
+}
@@ -0,0 +1,190 @@
+//! SSH local port forwarding to reach nodes behind a bastion host (`--ssh`).
+use anyhow::Context;
+use erl_dist::node::NodeName;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// Long enough to type a password or a passphrase if `ssh` asks for it.
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Number of the last stderr lines of `ssh` reported when it exits.
+const STDERR_TAIL_LINES: usize = 10;
+
+/// An `ssh -N -L LOCAL_PORT:HOST:PORT DESTINATION` child process.
+///
+/// The process is killed when this is dropped.
+#[derive(Debug)]
+pub struct SshTunnel {
+    child: Child,
+    local_port: u16,
+    remote_port: u16,
+    stderr: Option<JoinHandle<VecDeque<String>>>,
+}
+
+impl SshTunnel {
+    /// Forwards a local port to the distribution port of `erlang_node` via `destination` (e.g., `user@bastion`).
+    ///
+    /// If `port` is `None`, the distribution port is looked up from the EPMD on the node's host
+    /// through another temporary tunnel.
+    pub fn open_for_node(
+        destination: &str,
+        erlang_node: &NodeName,
+        port: Option<u16>,
+    ) -> anyhow::Result<Self> {
+        let port = match port {
+            Some(port) => port,
+            None => {
+                let epmd = Self::open(
+                    destination,
+                    erlang_node.host(),
+                    erl_dist::epmd::DEFAULT_EPMD_PORT,
+                )?;
+                epmd.lookup_node_port(erlang_node)?
+            }
+        };
+        Self::open(destination, erlang_node.host(), port)
+    }
+
+    /// Starts `ssh` and waits until it listens on the local port.
+    pub fn open(destination: &str, host: &str, port: u16) -> anyhow::Result<Self> {
+        let local_port = free_local_port()?;
+        let forward = format!("{local_port}:{host}:{port}");
+        log::debug!("starting ssh -L {forward} {destination}");
+        let mut child = Command::new("ssh")
+            .args(["-N", "-o", "ExitOnForwardFailure=yes", "-L", &forward])
+            .arg(destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to execute `ssh`")?;
+        let stderr = child.stderr.take().map(drain_stderr);
+        let mut tunnel = Self {
+            child,
+            local_port,
+            remote_port: port,
+            stderr,
+        };
+        tunnel
+            .wait_ready()
+            .with_context(|| format!("failed to forward port {forward} via {destination}"))?;
+        Ok(tunnel)
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    pub fn remote_port(&self) -> u16 {
+        self.remote_port
+    }
+
+    fn wait_ready(&mut self) -> anyhow::Result<()> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                let stderr = self
+                    .stderr
+                    .take()
+                    .and_then(|handle| handle.join().ok())
+                    .unwrap_or_default();
+                anyhow::bail!(
+                    "`ssh` exited with {status}: {}",
+                    Vec::from(stderr).join("\n")
+                );
+            }
+            // `ssh` accepts connections once the forwarding is set up.
+            // The probe connection is forwarded to the remote side and closed right away.
+            if is_listening(self.local_port) {
+                return Ok(());
+            }
+            anyhow::ensure!(
+                start.elapsed() < READY_TIMEOUT,
+                "timed out waiting for `ssh` to listen on port {}",
+                self.local_port
+            );
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn lookup_node_port(&self, erlang_node: &NodeName) -> anyhow::Result<u16> {
+        let port = smol::block_on(crate::erlang::find_node_port(
+            ("127.0.0.1", self.local_port),
+            erlang_node,
+        ))?;
+        Ok(port)
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.kill() {
+            log::warn!("failed to kill ssh: {e}");
+        }
+        let _ = self.child.wait();
+        log::debug!("closed ssh tunnel on port {}", self.local_port);
+    }
+}
+
+fn free_local_port() -> anyhow::Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+fn is_listening(port: u16) -> bool {
+    TcpStream::connect_timeout(
+        &SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        CONNECT_TIMEOUT,
+    )
+    .is_ok()
+}
+
+/// Reads the stderr of `ssh` until it exits, so that a chatty `ssh` never blocks on a full pipe.
+///
+/// The lines are logged and the last ones are returned to report why `ssh` exited.
+fn drain_stderr(stderr: impl 'static + Read + Send) -> JoinHandle<VecDeque<String>> {
+    std::thread::spawn(move || {
+        let mut tail = VecDeque::new();
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            log::debug!("ssh: {line}");
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+        tail
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_listening_works() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(is_listening(port));
+        drop(listener);
+        assert!(!is_listening(port));
+    }
+
+    #[test]
+    fn drain_stderr_keeps_last_lines() {
+        let output = (0..100).map(|i| format!("line {i}\n")).collect::<String>();
+        let tail = drain_stderr(std::io::Cursor::new(output)).join().unwrap();
+        assert_eq!(
+            Vec::from(tail),
+            (90..100).map(|i| format!("line {i}")).collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,114 +1,292 @@
-use crate::metrics::{format_u64, Header, MetricValue, Metrics, MetricsPoller};
-use crossterm::event::{KeyCode, KeyEvent};
+use crate::dashboard::{Dashboard, Panel};
+use crate::erlang::EndpointKind;
+use crate::export::format_timestamp;
+use crate::metrics::{
+    descriptions, float_decimals, format_bytes, format_f64, format_i64, format_micros_as_secs,
+    format_u64, format_utilization, is_bytes_metric, is_cpu_time_metric, set_float_decimals,
+    set_number_format, set_utilization_decimals, utilization_decimals, Header, MetricValue,
+    Metrics, MetricsPoller, BINARY_MEMORY_METRIC_NAME, LIMITED_RESOURCES, REDUCTIONS_METRIC_NAME,
+    RUN_QUEUE_METRIC_NAME, TOTAL_MEMORY_METRIC_NAME, TOTAL_UTILIZATION_METRIC_NAME,
+    UPTIME_METRIC_NAME,
+};
+use crate::UiArgs;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use erl_dist::node::NodeName;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use ratatui::style::{Modifier, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::symbols::Marker;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{
-    Axis, Block, Borders, Cell, Chart, Dataset, GraphType, Paragraph, Row, Table, TableState,
+    Axis, Bar, BarChart, BarGroup, Block, Borders, Cell, Chart, Clear, Dataset, Gauge, GraphType,
+    Paragraph, Row, Sparkline, Table, TableState, Wrap,
 };
 use ratatui::Frame;
-use std::collections::{BTreeMap, VecDeque};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::IsTerminal;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 type Terminal = ratatui::Terminal<ratatui::backend::CrosstermBackend<std::io::Stdout>>;
 
 const ONE_MINUTE: u64 = 60;
-const CHART_DURATION: u64 = ONE_MINUTE;
+pub(crate) const CHART_DURATION: u64 = ONE_MINUTE;
 const POLL_TIMEOUT: Duration = Duration::from_millis(10);
 
+// Minimum number of samples in the window to report a metric as steadily growing.
+const MIN_GROWTH_SAMPLES: usize = 10;
+
+// Title of the column showing the share of each child metric in its parent (e.g., `memory.total_bytes`).
+const SHARE_COLUMN_TITLE: &str = "% of total";
+
+// Range and step (in percent) of the left panel width adjusted by the '<' and '>' keys.
+const MIN_PANEL_PERCENTAGE: u16 = 20;
+const MAX_PANEL_PERCENTAGE: u16 = 80;
+const PANEL_SPLIT_STEP: u16 = 5;
+
+// Width (including the gap) of a cell of the run queue heatmap, and the minimum run queue length
+// drawn in the hottest color.
+const HEATMAP_CELL_WIDTH: u16 = 6;
+const HEATMAP_MIN_SCALE: f64 = 10.0;
+
+/// Colors assigned to the `utilization.*` metrics (thread types) in order of appearance.
+const PALETTE: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
 pub struct App {
     terminal: Terminal,
     poller: MetricsPoller,
     ui: UiState,
     replay_cursor_time: Duration,
+    clipboard: Option<arboard::Clipboard>,
+    shutdown: Arc<AtomicBool>,
+
+    // Redraws are deferred to the next frame so that fast polling doesn't cause flicker.
+    frame_interval: Duration,
+    last_frame: Instant,
+    needs_render: bool,
+
+    // `--snapshot-on-exit`.
+    snapshot_on_exit: Option<PathBuf>,
+
+    // `--pause-on-blur`, and whether the current pause was caused by the terminal losing focus
+    // (a pause by the 'p' key is kept when the focus comes back).
+    pause_on_blur: bool,
+    paused_on_blur: bool,
 }
 
 impl App {
-    pub fn new(poller: MetricsPoller) -> anyhow::Result<Self> {
+    /// Creates the dashboard. It exits when `shutdown` is set (e.g., by a signal handler).
+    ///
+    /// If `snapshot_on_exit` is given, the last metrics are written to the file when the dashboard exits.
+    pub fn new(
+        poller: MetricsPoller,
+        args: UiArgs,
+        shutdown: Arc<AtomicBool>,
+        snapshot_on_exit: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let dashboard = args
+            .dashboard
+            .as_deref()
+            .map(Dashboard::load_if_exists)
+            .transpose()?
+            .flatten();
         let terminal = Self::setup_terminal()?;
         log::debug!("setup terminal");
 
         let replay_mode = poller.is_replay();
-        let header = poller.header().clone();
+        set_number_format(args.number_format);
+        set_utilization_decimals(usize::from(args.util_decimals));
+        set_float_decimals(usize::from(args.float_decimals));
+        // See https://no-color.org/
+        let theme = if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            Theme::Mono
+        } else {
+            args.theme
+        };
+        let headers = poller.headers();
+        let duration = poller.duration();
+        let recording_paused = poller.is_recording_paused();
+        let markers = poller.markers().to_vec();
+        let compare = poller.is_compare();
+        let mut warnings = poller.warnings();
+        if let (Some(path), None) = (&args.dashboard, &dashboard) {
+            warnings.push(format!(
+                "{} not found, using the default layout",
+                path.display()
+            ));
+        }
+        // A followed record file is shown from its latest window.
+        let replay_cursor_time = if poller.is_following() {
+            poller
+                .replay_last_time()
+                .saturating_sub(Duration::from_secs(CHART_DURATION))
+        } else {
+            Duration::default()
+        };
+        let clipboard = arboard::Clipboard::new()
+            .map_err(|e| log::warn!("clipboard is not available: {e}"))
+            .ok();
         Ok(Self {
             terminal,
             poller,
-            ui: UiState::new(header, replay_mode),
-            replay_cursor_time: Duration::default(),
+            ui: UiState {
+                message: (!warnings.is_empty())
+                    .then(|| format!("WARNING: {}", warnings.join(", "))),
+                duration,
+                recording_paused,
+                markers,
+                compare,
+                sticky_widths: (!args.no_sticky_widths).then(RefCell::default),
+                header_spark: !args.no_header_spark,
+                smooth: args.smooth.map(|n| n as usize),
+                max_rows: args.max_rows.map(|n| n as usize),
+                hide_zero: args.hide_zero,
+                layout_mode: if args.compact {
+                    LayoutMode::Compact
+                } else if dashboard.is_some() {
+                    LayoutMode::Dashboard
+                } else {
+                    LayoutMode::Full
+                },
+                dashboard,
+                ..UiState::new(headers, replay_mode, args.columns, theme, args.retain)
+            },
+            replay_cursor_time,
+            clipboard,
+            shutdown,
+            frame_interval: Duration::from_secs(1) / args.ui_fps,
+            last_frame: Instant::now(),
+            needs_render: false,
+            snapshot_on_exit,
+            pause_on_blur: args.pause_on_blur && !replay_mode,
+            paused_on_blur: false,
         })
     }
 
     pub fn run(mut self) -> anyhow::Result<()> {
+        // The snapshot is also written if the dashboard exits due to an error (e.g., the node is lost).
+        let result = self.run_loop();
+        if let Some(path) = &self.snapshot_on_exit {
+            let nodes = self
+                .ui
+                .nodes
+                .iter()
+                .map(|node| (&node.header, node.history.back()))
+                .collect::<Vec<_>>();
+            if let Err(e) = crate::export::write_snapshot(path, &nodes) {
+                // Don't hide the error that terminated the dashboard.
+                if result.is_ok() {
+                    return Err(e);
+                }
+                log::warn!("{e}");
+            }
+        }
+        result
+    }
+
+    fn run_loop(&mut self) -> anyhow::Result<()> {
         self.render_replay_ui_if_need()?;
         loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                log::debug!("shutdown requested");
+                break;
+            }
             if self.handle_event()? {
                 break;
             }
-            if self.ui.pause || self.ui.replay_mode {
+            if self.ui.replay_mode {
+                self.follow_replay()?;
                 std::thread::sleep(POLL_TIMEOUT);
-            } else {
-                self.handle_poll()?;
+            } else if self.ui.pause {
+                std::thread::sleep(POLL_TIMEOUT);
+            } else if self.handle_poll()? {
+                break;
+            }
+            // Keep the elapsed time of the waiting screen up to date.
+            self.needs_render |= self.ui.node().history.is_empty();
+            if self.needs_render && self.last_frame.elapsed() >= self.frame_interval {
+                self.draw()?;
             }
         }
         Ok(())
     }
 
-    fn handle_poll(&mut self) -> anyhow::Result<()> {
+    fn handle_poll(&mut self) -> anyhow::Result<bool> {
         match self.poller.poll_metrics(POLL_TIMEOUT) {
+            Err(mpsc::RecvTimeoutError::Disconnected) if self.poller.is_finished() => {
+                log::debug!("metrics polling finished");
+                return Ok(true);
+            }
             Err(mpsc::RecvTimeoutError::Disconnected) => {
                 anyhow::bail!("Erlang metrics polling thread terminated unexpectedly");
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {}
-            Ok(metrics) => {
-                log::debug!("recv new metrics");
-
-                for (name, item) in &metrics.items {
-                    if let Some(avg) = self.ui.averages.get_mut(name) {
-                        avg.add(item.clone());
-                    } else {
-                        self.ui
-                            .averages
-                            .insert(name.clone(), AvgValue::new(item.clone()));
-                    }
-                }
+            Ok((node_index, metrics)) => {
+                log::debug!("recv new metrics (node_index={node_index})");
 
-                let timestamp = metrics.timestamp;
-                self.ui.history.push_back(metrics);
-                while let Some(metrics) = self.ui.history.pop_front() {
-                    let duration = (timestamp - metrics.timestamp).as_secs();
-                    if duration <= CHART_DURATION {
-                        self.ui.history.push_front(metrics);
-                        break;
-                    }
-                    for (name, item) in metrics.items {
-                        self.ui
-                            .averages
-                            .get_mut(&name)
-                            .expect("unreachable")
-                            .sub(item.clone());
-                    }
-                    log::debug!("remove old metrics");
+                self.ui
+                    .keep_selection(|ui| ui.nodes[node_index].push_metrics(metrics));
+                if node_index == self.ui.node_index {
+                    self.ui.sync_elapsed();
+                    self.render_ui()?;
                 }
-                self.ui.elapsed = self.ui.start.elapsed();
-                self.render_ui()?;
             }
         }
-        Ok(())
+        Ok(false)
+    }
+
+    /// Loads the metrics appended to the record file in `replay --follow` mode.
+    ///
+    /// The window moves along with the new metrics only if it was showing the latest ones.
+    fn follow_replay(&mut self) -> anyhow::Result<()> {
+        let last_time = self.poller.replay_last_time();
+        if !self.poller.follow_replay()? {
+            return Ok(());
+        }
+        if self.replay_cursor_time + Duration::from_secs(CHART_DURATION) >= last_time {
+            self.replay_cursor_time = self
+                .poller
+                .replay_last_time()
+                .saturating_sub(Duration::from_secs(CHART_DURATION));
+        }
+        self.ui.markers = self.poller.markers().to_vec();
+        self.render_replay_ui_if_need()
     }
 
     fn handle_event(&mut self) -> anyhow::Result<bool> {
         while crossterm::event::poll(std::time::Duration::from_secs(0))? {
             match crossterm::event::read()? {
-                crossterm::event::Event::Key(key) => {
-                    if self.handle_key_event(key)? {
-                        return Ok(true);
-                    }
+                // Windows also reports key release events, which must not trigger actions twice.
+                crossterm::event::Event::Key(key) if key.kind != KeyEventKind::Press => {}
+                crossterm::event::Event::Key(key) if self.handle_key_event(key)? => {
+                    return Ok(true);
                 }
                 crossterm::event::Event::Resize(_, _) => {
                     self.render_ui()?;
                 }
+                crossterm::event::Event::FocusLost if self.pause_on_blur && !self.ui.pause => {
+                    self.ui.pause = true;
+                    self.paused_on_blur = true;
+                    self.poller.set_polling_paused(true);
+                    self.render_ui()?;
+                }
+                crossterm::event::Event::FocusGained if self.paused_on_blur => {
+                    self.ui.pause = false;
+                    self.paused_on_blur = false;
+                    self.poller.set_polling_paused(false);
+                    self.render_ui()?;
+                }
                 _ => {}
             }
         }
@@ -116,13 +294,148 @@ impl App {
     }
 
     fn handle_key_event(&mut self, key: KeyEvent) -> anyhow::Result<bool> {
+        self.ui.message = None;
+        if self.ui.prompt.is_some() {
+            self.handle_prompt_key_event(key);
+            self.render_ui()?;
+            return Ok(false);
+        }
         match key.code {
             KeyCode::Char('q') => {
                 return Ok(true);
             }
+            // In raw mode, Ctrl-C is delivered as a key event instead of SIGINT.
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(true);
+            }
             KeyCode::Char('p') => {
                 self.ui.pause = !self.ui.pause;
             }
+            KeyCode::Char('n') if self.ui.replay_mode && !self.ui.markers.is_empty() => {
+                self.jump_to_marker(true)?;
+            }
+            KeyCode::Char('N') if self.ui.replay_mode && !self.ui.markers.is_empty() => {
+                self.jump_to_marker(false)?;
+            }
+            KeyCode::Char('n') => {
+                self.ui.node_index = (self.ui.node_index + 1) % self.ui.nodes.len();
+                self.ui.sync_elapsed();
+            }
+            KeyCode::Char('m') if self.ui.recording_paused.is_some() => {
+                self.ui.prompt = Some(Prompt::new(PromptKind::Marker));
+            }
+            KeyCode::Char('d') => {
+                if let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) {
+                    if !self.ui.delta_metrics.remove(&name) {
+                        self.ui.delta_metrics.insert(name);
+                    }
+                }
+            }
+            KeyCode::Char('r') if self.ui.smooth.is_some() => {
+                self.ui.show_raw_series = !self.ui.show_raw_series;
+            }
+            KeyCode::Char('L') => {
+                if let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) {
+                    if !self.ui.log_scale_metrics.remove(&name) {
+                        self.ui.log_scale_metrics.insert(name);
+                    }
+                }
+            }
+            KeyCode::Char('a') => {
+                if let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) {
+                    let is_counter = self
+                        .ui
+                        .latest_metrics()
+                        .and_then(|metrics| metrics.items.get(&name))
+                        .is_some_and(|value| value.raw_value().is_some());
+                    if is_counter && !self.ui.cumulative_metrics.remove(&name) {
+                        self.ui.cumulative_metrics.insert(name);
+                    }
+                }
+            }
+            KeyCode::Char('z') => {
+                self.ui.keep_selection(|ui| ui.hide_zero = !ui.hide_zero);
+            }
+            KeyCode::Char('y') => {
+                self.copy_selected_series();
+            }
+            KeyCode::Char('s') => {
+                self.ui
+                    .keep_selection(|ui| ui.sort_order = ui.sort_order.next());
+            }
+            KeyCode::Char('b') => {
+                if let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) {
+                    let chart_type = self.ui.chart_type(&name).next();
+                    if chart_type == ChartType::default() {
+                        self.ui.chart_types.remove(&name);
+                    } else {
+                        self.ui.chart_types.insert(name, chart_type);
+                    }
+                }
+            }
+            KeyCode::Char('B') => {
+                self.toggle_baseline();
+            }
+            KeyCode::Char('t') => {
+                if let Some(name) = self.ui.selected_metric_name() {
+                    let mut prompt = Prompt::new(PromptKind::Threshold);
+                    if let Some(threshold) = self.ui.thresholds.get(name) {
+                        prompt.input = threshold.to_string();
+                    }
+                    self.ui.prompt = Some(prompt);
+                }
+            }
+            KeyCode::Char('o') if !self.ui.replay_mode => {
+                self.ui.prompt = Some(Prompt::new(PromptKind::Node));
+            }
+            KeyCode::Char('x') if !self.ui.replay_mode => {
+                self.ui.clear_history();
+            }
+            KeyCode::Char('R') => {
+                self.poller.toggle_recording();
+                self.ui.recording_paused = self.poller.is_recording_paused();
+            }
+            KeyCode::Char('?') => {
+                self.ui.show_description = !self.ui.show_description;
+            }
+            KeyCode::Char('g') => {
+                self.ui.show_limits = !self.ui.show_limits;
+            }
+            KeyCode::Char('i') => {
+                self.ui.show_node_info = !self.ui.show_node_info;
+            }
+            KeyCode::Char('H') => {
+                self.ui.show_heatmap = !self.ui.show_heatmap;
+            }
+            KeyCode::Char('k') => {
+                self.ui.show_keys = !self.ui.show_keys;
+            }
+            KeyCode::Esc => {
+                self.ui.show_keys = false;
+                self.ui.show_description = false;
+                self.ui.show_limits = false;
+                self.ui.show_node_info = false;
+                self.ui.show_heatmap = false;
+            }
+            KeyCode::Char('<') => {
+                self.ui.left_panel_percentage = self
+                    .ui
+                    .left_panel_percentage
+                    .saturating_sub(PANEL_SPLIT_STEP)
+                    .max(MIN_PANEL_PERCENTAGE);
+            }
+            KeyCode::Char('>') => {
+                self.ui.left_panel_percentage =
+                    (self.ui.left_panel_percentage + PANEL_SPLIT_STEP).min(MAX_PANEL_PERCENTAGE);
+            }
+            KeyCode::Char('c') => {
+                self.ui.layout_mode = match self.ui.layout_mode {
+                    LayoutMode::Full => LayoutMode::Compact,
+                    LayoutMode::Compact if self.ui.dashboard.is_some() => LayoutMode::Dashboard,
+                    LayoutMode::Compact | LayoutMode::Dashboard => LayoutMode::Full,
+                };
+                self.ui.focus = Focus::Main;
+            }
             KeyCode::Char('h') => {
                 self.replay_cursor_time = self
                     .replay_cursor_time
@@ -141,7 +454,9 @@ impl App {
                 self.ui.focus = Focus::Main;
             }
             KeyCode::Right => {
-                self.ui.focus = Focus::Sub;
+                if self.ui.layout_mode == LayoutMode::Full {
+                    self.ui.focus = Focus::Sub;
+                }
             }
             KeyCode::Up => {
                 let table = if self.ui.focus == Focus::Main {
@@ -171,10 +486,155 @@ impl App {
         Ok(false)
     }
 
+    fn handle_prompt_key_event(&mut self, key: KeyEvent) {
+        let Some(prompt) = &mut self.ui.prompt else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Esc => {
+                self.ui.prompt = None;
+            }
+            KeyCode::Enter => {
+                let Some(prompt) = self.ui.prompt.take() else {
+                    return;
+                };
+                match prompt.kind {
+                    PromptKind::Node => self.switch_node(prompt.input.trim()),
+                    PromptKind::Marker => self.add_marker(prompt.input.trim()),
+                    PromptKind::Threshold => self.set_threshold(prompt.input.trim()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the threshold line of the selected metric, or removes it if `input` is empty.
+    fn set_threshold(&mut self, input: &str) {
+        let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) else {
+            return;
+        };
+        if input.is_empty() {
+            self.ui.thresholds.remove(&name);
+            self.ui.message = Some(format!("Removed the threshold of {name:?}"));
+            return;
+        }
+        match input.parse::<f64>() {
+            Ok(threshold) if threshold.is_finite() => {
+                self.ui.thresholds.insert(name, threshold);
+            }
+            _ => {
+                self.ui.message = Some(format!("Invalid threshold: {input:?}"));
+            }
+        }
+    }
+
+    fn add_marker(&mut self, label: &str) {
+        self.ui.message = Some(match self.poller.add_marker(label) {
+            Ok(()) => format!("Added marker {label:?}"),
+            Err(e) => format!("Failed to add marker: {e}"),
+        });
+    }
+
+    /// Moves the replay window so that the next (or previous) marker is at its center.
+    fn jump_to_marker(&mut self, forward: bool) -> anyhow::Result<()> {
+        let half_window = Duration::from_secs(CHART_DURATION / 2);
+        let center = self.replay_cursor_time + half_window;
+        let markers = &self.ui.markers;
+        let marker = if forward {
+            markers.iter().find(|m| m.timestamp > center)
+        } else {
+            markers.iter().rev().find(|m| m.timestamp < center)
+        };
+        let Some(marker) = marker else {
+            self.ui.message = Some("No more markers".to_owned());
+            return Ok(());
+        };
+        self.ui.message = Some(format!("Marker {:?}", marker.marker));
+        self.replay_cursor_time = marker.timestamp.saturating_sub(half_window);
+        self.render_replay_ui_if_need()
+    }
+
+    fn switch_node(&mut self, node_name: &str) {
+        let result = node_name
+            .parse::<NodeName>()
+            .map_err(anyhow::Error::from)
+            .and_then(|node_name| self.poller.switch_node(self.ui.node_index, node_name));
+        match result {
+            Ok(header) => {
+                let node = &mut self.ui.nodes[self.ui.node_index];
+                *node = NodeState::new(header, node.retain);
+                self.ui.sync_elapsed();
+                self.ui.message = Some(format!("Connected to {node_name}"));
+            }
+            Err(e) => {
+                log::warn!("failed to switch to {node_name}: {e}");
+                self.ui.message = Some(format!("Failed to connect to {node_name}: {e}"));
+            }
+        }
+    }
+
+    /// Captures the charted series of the selected metric as its baseline, or clears the baseline if
+    /// one is already captured.
+    fn toggle_baseline(&mut self) {
+        let Some(name) = self.ui.selected_metric_name().map(|x| x.to_owned()) else {
+            return;
+        };
+        if self.ui.baselines.remove(&name).is_some() {
+            self.ui.message = Some(format!("Cleared the baseline of {name:?}"));
+            return;
+        }
+        let Some((_, data)) = self.ui.chart_data().filter(|(_, data)| !data.is_empty()) else {
+            return;
+        };
+        self.ui.baselines.insert(name.clone(), data);
+        self.ui.message = Some(format!("Captured the baseline of {name:?}"));
+    }
+
+    fn copy_selected_series(&mut self) {
+        let Some((metric_name, tsv)) = self.ui.selected_series_tsv() else {
+            return;
+        };
+
+        if let Some(clipboard) = &mut self.clipboard {
+            match clipboard.set_text(tsv.clone()) {
+                Ok(()) => {
+                    self.ui.message = Some(format!("Copied {metric_name:?} to clipboard"));
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("failed to copy to clipboard: {e}");
+                }
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "erldash-{metric_name}-{}.tsv",
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        ));
+        self.ui.message = Some(match std::fs::write(&path, tsv) {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(e) => format!("Failed to write {}: {e}", path.display()),
+        });
+    }
+
+    /// Requests a redraw, which happens at the next frame.
     fn render_ui(&mut self) -> anyhow::Result<()> {
-        if !self.ui.history.is_empty() {
+        self.needs_render = true;
+        Ok(())
+    }
+
+    fn draw(&mut self) -> anyhow::Result<()> {
+        if self.ui.node().history.is_empty() {
+            self.terminal.draw(|f| self.ui.render_waiting(f))?;
+        } else {
             self.terminal.draw(|f| self.ui.render(f))?;
         }
+        self.needs_render = false;
+        self.last_frame = Instant::now();
         Ok(())
     }
 
@@ -184,41 +644,40 @@ impl App {
         }
 
         let time = self.replay_cursor_time;
-
-        self.ui.history.clear();
-        self.ui.averages.clear();
-        for metrics in self
-            .poller
-            .get_metrics_range(time, time + Duration::from_secs(CHART_DURATION))?
-        {
-            self.ui.history.push_back(metrics.clone());
-
-            for (name, item) in &metrics.items {
-                if let Some(avg) = self.ui.averages.get_mut(name) {
-                    avg.add(item.clone());
-                } else {
-                    self.ui
-                        .averages
-                        .insert(name.clone(), AvgValue::new(item.clone()));
-                }
+        let end = time + Duration::from_secs(CHART_DURATION);
+
+        // All nodes are moved so that compared recordings stay aligned.
+        let poller = &self.poller;
+        self.ui.keep_selection(|ui| {
+            for (node_index, node) in ui.nodes.iter_mut().enumerate() {
+                node.slide_window(end, |start, end| {
+                    Ok(poller
+                        .get_metrics_range(node_index, start, end)?
+                        .cloned()
+                        .collect())
+                })?;
             }
-        }
+            anyhow::Ok(())
+        })?;
 
-        self.ui.elapsed = self
-            .ui
-            .history
-            .back()
-            .map(|x| x.timestamp)
-            .unwrap_or_default();
+        self.ui.sync_elapsed();
 
         self.render_ui()?;
         Ok(())
     }
 
     fn setup_terminal() -> anyhow::Result<Terminal> {
-        crossterm::terminal::enable_raw_mode()?;
         let mut stdout = std::io::stdout();
-        crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen,)?;
+        anyhow::ensure!(
+            stdout.is_terminal(),
+            "stdout is not a terminal. Use `erldash dump` or `erldash replay --to-csv` for non-interactive output"
+        );
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            stdout,
+            crossterm::terminal::EnterAlternateScreen,
+            crossterm::event::EnableFocusChange,
+        )?;
         let backend = ratatui::backend::CrosstermBackend::new(stdout);
         let terminal = ratatui::Terminal::new(backend)?;
         Ok(terminal)
@@ -228,6 +687,7 @@ impl App {
         crossterm::terminal::disable_raw_mode()?;
         crossterm::execute!(
             self.terminal.backend_mut(),
+            crossterm::event::DisableFocusChange,
             crossterm::terminal::LeaveAlternateScreen,
         )?;
         self.terminal.show_cursor()?;
@@ -247,31 +707,141 @@ impl Drop for App {
 
 #[derive(Debug)]
 struct UiState {
-    start: Instant,
-    header: Header,
+    // When the dashboard started waiting for the first sample (shown on the waiting screen).
+    waiting_since: Instant,
+    nodes: Vec<NodeState>,
+    node_index: usize,
+
+    // Offset of the latest metrics of the displayed node from the start of its run (see `sync_elapsed()`).
     elapsed: Duration,
     pause: bool,
-    history: VecDeque<Metrics>,
-    averages: BTreeMap<String, AvgValue>,
     focus: Focus,
     metrics_table_state: TableState,
     detail_table_state: TableState,
     replay_mode: bool,
+    columns: Vec<Column>,
+    theme: Theme,
+    message: Option<String>,
+    delta_metrics: BTreeSet<String>,
+    log_scale_metrics: BTreeSet<String>,
+
+    // Counters whose cumulative values are charted instead of their rates (toggled by the 'a' key).
+    cumulative_metrics: BTreeSet<String>,
+
+    // Values at which a horizontal line is drawn on the chart of each metric (set by the 't' key).
+    thresholds: BTreeMap<String, f64>,
+
+    // Series frozen by the 'B' key, which are drawn dimly behind the live series of each metric.
+    baselines: BTreeMap<String, Vec<(f64, f64)>>,
+    chart_types: BTreeMap<String, ChartType>,
+    show_description: bool,
+    show_limits: bool,
+    show_node_info: bool,
+    show_heatmap: bool,
+
+    // Whether the list of all keys is shown over the dashboard ('k' key).
+    show_keys: bool,
+    layout_mode: LayoutMode,
+    duration: Option<Duration>,
+
+    // `None` if metrics are not being recorded.
+    recording_paused: Option<bool>,
+
+    // Text being typed after the 'o' or 'm' key is pressed.
+    prompt: Option<Prompt>,
+
+    // Markers of the replayed record file.
+    markers: Vec<crate::metrics::Marker>,
+
+    // Whether nodes 0 and 1 are the two record files given to `replay --compare`.
+    compare: bool,
+
+    sort_order: SortOrder,
+
+    // Whether metrics whose latest value is zero are left out of the tables (toggled by the 'z' key).
+    hide_zero: bool,
+
+    // Width of the left panel of the body in percent (changed by the '<' and '>' keys).
+    left_panel_percentage: u16,
+
+    // High-water marks of the column widths per table, so that columns don't jump around as
+    // numbers change magnitude (`None` if `--no-sticky-widths` is specified).
+    sticky_widths: Option<RefCell<BTreeMap<Focus, Vec<usize>>>>,
+
+    // Maximum number of rendered rows per table (`--max-rows`), and the index of the first rendered
+    // row of each table that is scrolled.
+    max_rows: Option<usize>,
+    row_offsets: RefCell<BTreeMap<Focus, usize>>,
+
+    // Indices into `PALETTE` of the `utilization.*` root metrics, which are kept for the whole
+    // session so that each thread type has the same color in the tables and the chart.
+    utilization_colors: BTreeMap<String, usize>,
+
+    // Whether the sparkline of `utilization.total` is shown in the header (disabled by `--no-header-spark`).
+    header_spark: bool,
+
+    // Number of samples the counter rates are averaged over (`--smooth`).
+    smooth: Option<usize>,
+
+    // Whether the raw rates are drawn under the smoothed ones in the chart (toggled by the 'r' key).
+    show_raw_series: bool,
+
+    // Panels of `--dashboard` shown in `LayoutMode::Dashboard`.
+    dashboard: Option<Dashboard>,
 }
 
 impl UiState {
-    fn new(header: Header, replay_mode: bool) -> Self {
+    fn new(
+        headers: Vec<Header>,
+        replay_mode: bool,
+        columns: Vec<Column>,
+        theme: Theme,
+        retain: Duration,
+    ) -> Self {
         Self {
-            start: Instant::now(),
-            header,
+            waiting_since: Instant::now(),
+            nodes: headers
+                .into_iter()
+                .map(|header| NodeState::new(header, retain))
+                .collect(),
+            node_index: 0,
             elapsed: Duration::default(),
             pause: false,
-            history: VecDeque::new(),
-            averages: BTreeMap::new(),
             focus: Focus::Main,
             metrics_table_state: TableState::default(),
             detail_table_state: TableState::default(),
             replay_mode,
+            columns,
+            theme,
+            message: None,
+            delta_metrics: BTreeSet::new(),
+            log_scale_metrics: BTreeSet::new(),
+            cumulative_metrics: BTreeSet::new(),
+            thresholds: BTreeMap::new(),
+            baselines: BTreeMap::new(),
+            chart_types: BTreeMap::new(),
+            show_description: false,
+            show_keys: false,
+            show_limits: false,
+            show_node_info: false,
+            show_heatmap: false,
+            layout_mode: LayoutMode::Full,
+            duration: None,
+            recording_paused: None,
+            prompt: None,
+            markers: Vec::new(),
+            compare: false,
+            sort_order: SortOrder::Name,
+            hide_zero: false,
+            left_panel_percentage: 50,
+            sticky_widths: None,
+            max_rows: None,
+            row_offsets: RefCell::default(),
+            utilization_colors: BTreeMap::new(),
+            header_spark: false,
+            smooth: None,
+            show_raw_series: true,
+            dashboard: None,
         }
     }
 
@@ -283,44 +853,218 @@ impl UiState {
 
         self.render_header(f, chunks[0]);
         self.render_body(f, chunks[1]);
+        if self.show_limits {
+            self.render_limits(f, chunks[1]);
+        }
+        if self.show_node_info {
+            self.render_node_info(f, chunks[1]);
+        }
+        if self.show_heatmap {
+            self.render_run_queue_heatmap(f, chunks[1]);
+        }
+        if self.show_description {
+            self.render_description(f, chunks[1]);
+        }
+        if self.show_keys {
+            self.render_keys(f, chunks[1]);
+        }
+        if self.prompt.is_some() {
+            self.render_prompt(f, chunks[1]);
+        }
+    }
+
+    /// Renders a placeholder until the first metrics of the displayed node arrive.
+    fn render_waiting(&mut self, f: &mut Frame) {
+        let node_name = &self.node().header.node_name;
+        let lines = if self.replay_mode {
+            vec![Line::from("No metrics are recorded in this range")]
+        } else {
+            let title = self
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("Connected to {node_name}"));
+            let endpoint = self
+                .node()
+                .header
+                .endpoint
+                .as_ref()
+                .map(|endpoint| endpoint.to_string())
+                .unwrap_or_default();
+            vec![
+                Line::from(title),
+                Line::from(endpoint),
+                Line::from(format!(
+                    "Waiting for the first sample... ({}s)",
+                    self.waiting_since.elapsed().as_secs()
+                )),
+            ]
+        };
+        let paragraph = Paragraph::new(lines)
+            .block(self.make_block("erldash"))
+            .alignment(Alignment::Center);
+        let area = popup_area(f.size(), 5);
+        f.render_widget(paragraph, area);
     }
 
     fn render_header(&mut self, f: &mut Frame, area: Rect) {
+        // The CPU box is widened at the expense of the system version to make room for the sparkline.
+        let (version_percentage, cpu_percentage) = if self.header_spark {
+            (20, 20)
+        } else {
+            (30, 10)
+        };
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints(
                 [
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(60),
-                    Constraint::Percentage(20),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(version_percentage),
+                    Constraint::Percentage(cpu_percentage),
+                    Constraint::Percentage(12),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(18),
                 ]
                 .as_ref(),
             )
             .split(area);
 
-        let paragraph = Paragraph::new(vec![Line::from(self.header.node_name.clone())])
-            .block(self.make_block("Node"))
+        let mut node_name = if self.nodes.len() > 1 {
+            format!(
+                "{} ({}/{})",
+                self.node().header.node_name,
+                self.node_index + 1,
+                self.nodes.len()
+            )
+        } else {
+            self.node().header.node_name.clone()
+        };
+        let mut tags = Vec::new();
+        match self.recording_paused {
+            None => {}
+            Some(false) => tags.push("REC"),
+            Some(true) => tags.push("PAUSED REC"),
+        }
+        match self.node().header.endpoint.as_ref().map(|e| &e.kind) {
+            Some(EndpointKind::Direct) => tags.push("direct"),
+            Some(EndpointKind::Ssh(_)) => tags.push("SSH"),
+            Some(EndpointKind::Epmd) | None => {}
+        }
+        let node_title = if tags.is_empty() {
+            "Node".to_owned()
+        } else {
+            format!("Node ({})", tags.join(", "))
+        };
+        if let Some(endpoint) = &self.node().header.endpoint {
+            node_name = format!("{node_name} :{}", endpoint.port);
+        }
+        let paragraph = Paragraph::new(vec![Line::from(node_name)])
+            .block(self.make_block(&node_title))
             .alignment(Alignment::Left);
         f.render_widget(paragraph, chunks[0]);
 
-        let paragraph = Paragraph::new(vec![Line::from(self.header.system_version.get())])
+        let paragraph = Paragraph::new(vec![Line::from(self.node().header.system_version.get())])
             .block(self.make_block("System Version"))
             .alignment(Alignment::Left);
         f.render_widget(paragraph, chunks[1]);
 
-        let now = self.header.start_time + self.elapsed;
+        let utilization = self.latest_metrics().and_then(|metrics| {
+            metrics
+                .items
+                .get(TOTAL_UTILIZATION_METRIC_NAME)
+                .and_then(|v| v.as_f64())
+        });
+        let text = utilization
+            .map(|utilization| {
+                Span::styled(
+                    format_utilization(utilization),
+                    self.theme.utilization_style(utilization),
+                )
+            })
+            .unwrap_or_default();
+        let block = self.make_block("CPU");
+        let inner = block.inner(chunks[2]);
+        f.render_widget(block, chunks[2]);
+        let text_width = text.width() as u16 + 1;
+        let paragraph = Paragraph::new(vec![Line::from(text)]).alignment(Alignment::Left);
+        f.render_widget(paragraph, inner);
+        if self.header_spark && inner.width > text_width {
+            let spark_area = Rect {
+                x: inner.x + text_width,
+                width: inner.width - text_width,
+                ..inner
+            };
+            self.render_utilization_spark(f, spark_area);
+        }
+
+        let uptime = self
+            .latest_metrics()
+            .and_then(|metrics| metrics.items.get(UPTIME_METRIC_NAME))
+            .and_then(|v| v.as_f64())
+            .map(|ms| format_uptime(Duration::from_millis(ms as u64)))
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(vec![Line::from(uptime)])
+            .block(self.make_block("Uptime"))
+            .alignment(Alignment::Left);
+        f.render_widget(paragraph, chunks[3]);
+
+        let reductions = self
+            .latest_metrics()
+            .and_then(|metrics| metrics.items.get(REDUCTIONS_METRIC_NAME))
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(vec![Line::from(reductions)])
+            .block(self.make_block("Reductions"))
+            .alignment(Alignment::Left);
+        f.render_widget(paragraph, chunks[4]);
+
+        let now = self.header_time();
         let paragraph = Paragraph::new(vec![Line::from(
             now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
         )])
         .block(self.make_block("Time"))
         .alignment(Alignment::Left);
-        f.render_widget(paragraph, chunks[2]);
+        f.render_widget(paragraph, chunks[5]);
+    }
+
+    /// Renders the `utilization.total` values of the latest polls (one per column) as a sparkline.
+    fn render_utilization_spark(&self, f: &mut Frame, area: Rect) {
+        let values = self
+            .node()
+            .history
+            .iter()
+            .filter_map(|metrics| metrics.items.get(TOTAL_UTILIZATION_METRIC_NAME)?.as_f64())
+            .map(|utilization| utilization.round() as u64)
+            .collect::<Vec<_>>();
+        let values = &values[values.len().saturating_sub(usize::from(area.width))..];
+        let style = values
+            .last()
+            .map_or_else(Style::default, |v| self.theme.utilization_style(*v as f64));
+        let sparkline = Sparkline::default().data(values).max(100).style(style);
+        f.render_widget(sparkline, area);
     }
 
     fn render_body(&mut self, f: &mut Frame, area: Rect) {
+        match self.layout_mode {
+            LayoutMode::Full => {}
+            LayoutMode::Compact => {
+                self.render_body_left(f, area);
+                return;
+            }
+            LayoutMode::Dashboard => {
+                self.render_dashboard(f, area);
+                return;
+            }
+        }
+
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .constraints(
+                [
+                    Constraint::Percentage(self.left_panel_percentage),
+                    Constraint::Percentage(100 - self.left_panel_percentage),
+                ]
+                .as_ref(),
+            )
             .split(area);
 
         self.render_body_left(f, chunks[0]);
@@ -330,76 +1074,122 @@ impl UiState {
     fn render_body_left(&mut self, f: &mut Frame, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Min(0), Constraint::Length(5)].as_ref())
+            .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
             .split(area);
         self.render_metrics(f, chunks[0]);
         self.render_help(f, chunks[1]);
     }
 
     fn render_metrics(&mut self, f: &mut Frame, area: Rect) {
-        let block = if self.replay_mode {
-            self.make_block("Metrics (REPLAY)")
+        let mut title = if self.compare {
+            "Metrics (COMPARE)".to_owned()
+        } else if self.replay_mode {
+            "Metrics (REPLAY)".to_owned()
         } else if self.pause {
-            self.make_block("Metrics (PAUSED)")
+            "Metrics (PAUSED)".to_owned()
+        } else if let Some(duration) = self.duration {
+            let remaining = duration.saturating_sub(self.elapsed);
+            format!("Metrics (stops in {}s)", remaining.as_secs())
         } else {
-            self.make_block("Metrics")
+            "Metrics".to_owned()
         };
-
-        let header_cells = ["Name", "Value", "Avg (1m)"]
-            .into_iter()
-            .map(|h| Cell::from(h).style(Style::default().add_modifier(Modifier::BOLD)));
-        let header = Row::new(header_cells).bottom_margin(1);
-
-        let items = self.latest_metrics().root_items().collect::<Vec<_>>();
-        let is_avg_available = self.elapsed.as_secs() >= (ONE_MINUTE - 1);
-        let mut value_width = 0;
-        let mut avg_width = 0;
-        let mut row_items = Vec::with_capacity(items.len());
-        for (name, item) in &items {
-            let value = item.to_string();
-            let avg = if is_avg_available {
-                self.averages
-                    .get(*name)
-                    .map(|v| v.get().to_string())
-                    .unwrap_or("".to_string())
-            } else {
-                "".to_string()
-            };
-            value_width = std::cmp::max(value_width, value.len());
-            avg_width = std::cmp::max(avg_width, avg.len());
-            row_items.push((name.to_string(), value, avg));
+        if self.sort_order != SortOrder::Name {
+            title += &format!(" [sorted by {}]", self.sort_order.name());
         }
+        if self.hide_zero {
+            title += " [zeros hidden]";
+        }
+        let block = self.make_block(&title);
 
-        let rows = row_items.into_iter().map(|(name, value, avg)| {
-            Row::new(vec![
-                Cell::from(name),
-                Cell::from(format!("{:>value_width$}", value)),
-                Cell::from(format!("{:>avg_width$}", avg)),
-            ])
-        });
-
-        let widths = [
-            Constraint::Percentage(50),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-        ];
-        let highlight_style = if self.focus == Focus::Main {
-            Style::default().add_modifier(Modifier::REVERSED)
-        } else {
-            Style::default()
-        };
+        self.assign_utilization_colors();
+        let row_count = self.root_names().len();
         let selected = std::cmp::min(
             self.metrics_table_state.selected().unwrap_or(0),
-            items.len().saturating_sub(1),
+            row_count.saturating_sub(1),
         );
         self.metrics_table_state.select(Some(selected));
+        let window = self.row_window(Focus::Main, row_count, selected, area);
 
-        let table = Table::new(rows, widths)
-            .header(header)
-            .block(block)
-            .highlight_style(highlight_style)
-            .highlight_symbol("> ");
-        f.render_stateful_widget(table, area, &mut self.metrics_table_state);
+        let names = self.root_names();
+        let names = &names[window.clone()];
+        let items = self
+            .latest_metrics()
+            .map(|metrics| {
+                names
+                    .iter()
+                    .filter_map(|name| Some((*name, metrics.items.get(*name)?)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let is_avg_available = self.is_avg_available();
+        let (header, mut rows, widths) = if self.compare {
+            self.make_compare_table_contents(Focus::Main, names)
+        } else {
+            self.make_table_contents(Focus::Main, &items, is_avg_available, None)
+        };
+        rows.extend(self.overflow_row(row_count, &window));
+
+        let highlight_style = if self.focus == Focus::Main {
+            self.theme.highlight_style()
+        } else {
+            Style::default()
+        };
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .highlight_style(highlight_style)
+            .highlight_symbol("> ");
+        if window.len() == row_count {
+            f.render_stateful_widget(table, area, &mut self.metrics_table_state);
+        } else {
+            let mut state = TableState::default().with_selected(Some(selected - window.start));
+            f.render_stateful_widget(table, area, &mut state);
+        }
+    }
+
+    /// Returns the range of the `row_count` rows of `table` to render.
+    ///
+    /// With `--max-rows`, at most that many rows (and no more than fit in `area`) are rendered,
+    /// scrolled so that the selected row is visible. Otherwise, all rows are rendered.
+    fn row_window(
+        &self,
+        table: Focus,
+        row_count: usize,
+        selected: usize,
+        area: Rect,
+    ) -> std::ops::Range<usize> {
+        let Some(max_rows) = self.max_rows.filter(|max_rows| row_count > *max_rows) else {
+            return 0..row_count;
+        };
+
+        // The borders, the header and its margin take four lines, and the overflow row one more.
+        let visible = usize::from(area.height.saturating_sub(5)).max(1);
+        let len = std::cmp::min(max_rows, visible);
+        let mut offsets = self.row_offsets.borrow_mut();
+        let offset = offsets.entry(table).or_default();
+        if selected < *offset {
+            *offset = selected;
+        } else if selected >= *offset + len {
+            *offset = selected + 1 - len;
+        }
+        *offset = std::cmp::min(*offset, row_count - len);
+        *offset..*offset + len
+    }
+
+    /// Returns the "... N more" row shown under the rows in `window` if some rows are not rendered.
+    fn overflow_row(
+        &self,
+        row_count: usize,
+        window: &std::ops::Range<usize>,
+    ) -> Option<Row<'static>> {
+        let hidden = row_count - window.len();
+        (hidden > 0).then(|| {
+            Row::new(vec![Cell::from(format!(
+                "\u{2026} {hidden} more ({} above)",
+                window.start
+            ))])
+            .style(Style::default().add_modifier(Modifier::DIM))
+        })
     }
 
     fn render_body_right(&mut self, f: &mut Frame, area: Rect) {
@@ -412,94 +1202,686 @@ impl UiState {
         self.render_chart(f, chunks[1]);
     }
 
-    fn render_help(&mut self, f: &mut Frame, area: Rect) {
-        let paragraph = if self.replay_mode {
-            Paragraph::new(vec![
-                Line::from("Quit:           'q' key"),
-                Line::from("Prev / Next:    'h' / 'l' keys"),
-                Line::from("Move:           UP / DOWN / LEFT / RIGHT keys"),
-            ])
+    /// Renders the panels of `--dashboard` in a grid.
+    fn render_dashboard(&mut self, f: &mut Frame, area: Rect) {
+        self.assign_utilization_colors();
+        let Some(dashboard) = &self.dashboard else {
+            return;
+        };
+        let rows = dashboard.rows().collect::<Vec<_>>();
+        let row_areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Ratio(1, rows.len() as u32); rows.len()])
+            .split(area);
+        let mut index = 0;
+        for (panels, row_area) in rows.into_iter().zip(row_areas.iter()) {
+            let panel_areas = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![
+                    Constraint::Ratio(1, dashboard.columns.get() as u32);
+                    dashboard.columns.get()
+                ])
+                .split(*row_area);
+            for (panel, panel_area) in panels.iter().zip(panel_areas.iter()) {
+                self.render_panel(f, *panel_area, index, panel);
+                index += 1;
+            }
+        }
+    }
+
+    fn render_panel(&self, f: &mut Frame, area: Rect, index: usize, panel: &Panel) {
+        let chart = panel.chart.as_deref().and_then(|metric_name| {
+            let data = self.series_data(metric_name, &self.node().history)?;
+            Some((metric_name, data))
+        });
+        let area = if let Some((metric_name, data)) = chart {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Percentage(50)].as_ref())
+                .split(area);
+            self.render_metric_chart(f, chunks[1], metric_name, data);
+            chunks[0]
+        } else {
+            area
+        };
+
+        let items = self
+            .latest_metrics()
+            .map(|metrics| {
+                panel
+                    .select(metrics.items.keys().map(|name| name.as_str()))
+                    .into_iter()
+                    .filter_map(|name| Some((name, metrics.items.get(name)?)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let is_avg_available = self.is_avg_available();
+        let (header, rows, widths) =
+            self.make_table_contents(Focus::Panel(index), &items, is_avg_available, None);
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(self.make_block(&panel.title));
+        f.render_widget(table, area);
+    }
+
+    /// Drops the collected metrics so that the charts and averages start over from now.
+    fn clear_history(&mut self) {
+        for node in &mut self.nodes {
+            node.reset_window();
+        }
+        self.waiting_since = Instant::now();
+        if let Some(sticky_widths) = &self.sticky_widths {
+            sticky_widths.borrow_mut().clear();
+        }
+        self.message = Some("History cleared".to_owned());
+    }
+
+    /// Returns the lines of the list of all keys shown by the 'k' key.
+    fn key_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from("Quit:           'q' key")];
+        if self.replay_mode {
+            lines.push(Line::from("Prev / Next:    'h' / 'l' keys"));
         } else {
-            Paragraph::new(vec![
-                Line::from("Quit:           'q' key"),
-                Line::from("Pause / Resume: 'p' key"),
-                Line::from("Move:           UP / DOWN / LEFT / RIGHT keys"),
-            ])
+            lines.push(Line::from("Pause / Resume: 'p' key"));
         }
-        .block(self.make_block("Help"))
-        .alignment(Alignment::Left);
+        lines.push(Line::from("Move:           arrow keys"));
+        if self.replay_mode && !self.markers.is_empty() {
+            lines.push(Line::from("Markers:        'n' / 'N' keys"));
+        } else if self.nodes.len() > 1 {
+            lines.push(Line::from("Switch node:    'n' key"));
+        }
+        if !self.replay_mode {
+            lines.push(Line::from("Connect node:   'o' key"));
+        }
+        lines.push(Line::from("Delta / Value:  'd' key"));
+        lines.push(Line::from("Sort:           's' key"));
+        lines.push(Line::from("Hide zeros:     'z' key"));
+        lines.push(Line::from("Chart type:     'b' key"));
+        lines.push(Line::from("Log scale:      'L' key"));
+        lines.push(Line::from("Raw / Rate:     'a' key"));
+        lines.push(Line::from("Threshold/Base: 't' / 'B' keys"));
+        if self.smooth.is_some() {
+            lines.push(Line::from("Raw series:     'r' key"));
+        }
+        lines.push(Line::from("Copy series:    'y' key"));
+        if !self.replay_mode {
+            lines.push(Line::from("Clear history:  'x' key"));
+        }
+        if self.recording_paused.is_some() {
+            lines.push(Line::from("Pause record:   'R' key"));
+            lines.push(Line::from("Add marker:     'm' key"));
+        }
+        lines.push(Line::from("Describe:       '?' key"));
+        lines.push(Line::from("Limits:         'g' key"));
+        lines.push(Line::from("Node info:      'i' key"));
+        lines.push(Line::from("Run queue map:  'H' key"));
+        if self.dashboard.is_some() {
+            lines.push(Line::from("Layout:         'c' key"));
+        } else {
+            lines.push(Line::from("Compact / Full: 'c' key"));
+        }
+        if self.layout_mode == LayoutMode::Full {
+            lines.push(Line::from("Resize panels:  '<' / '>' keys"));
+        }
+        lines.push(Line::from("Close:          'k' / ESC keys"));
+        lines
+    }
+
+    /// Renders the list of all keys, split into two columns if it doesn't fit in the height of `area`.
+    fn render_keys(&mut self, f: &mut Frame, area: Rect) {
+        let lines = self.key_lines();
+        let columns = if lines.len() + 2 > usize::from(area.height) {
+            2
+        } else {
+            1
+        };
+        let rows = lines.len().div_ceil(columns);
+        let width = if columns == 1 {
+            area.width * 3 / 5
+        } else {
+            area.width
+        };
+        let height = std::cmp::min(area.height, rows as u16 + 2);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let block = self.make_block("Keys");
+        let inner = block.inner(popup);
+        f.render_widget(Clear, popup);
+        f.render_widget(block, popup);
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+            .split(inner);
+        for (chunk, lines) in chunks.iter().zip(lines.chunks(rows)) {
+            f.render_widget(Paragraph::new(lines.to_vec()), *chunk);
+        }
+    }
+
+    fn render_description(&mut self, f: &mut Frame, area: Rect) {
+        let Some(metric_name) = self.selected_metric_name() else {
+            return;
+        };
+        let lines = match descriptions::get(metric_name) {
+            Some(description) => vec![
+                Line::from(description.text),
+                Line::from(""),
+                Line::from(format!("Source: {}", description.source)),
+            ],
+            None => vec![Line::from("(no description)")],
+        };
+        let paragraph = Paragraph::new(lines)
+            .block(self.make_block(&format!("Description of {metric_name:?}")))
+            .wrap(Wrap { trim: true });
+
+        let area = popup_area(area, 10);
+        f.render_widget(Clear, area);
         f.render_widget(paragraph, area);
     }
 
-    fn chart_data(&self) -> (&str, Vec<(f64, f64)>) {
-        let root_metric_name = self
+    /// Renders the process/port/atom/ETS counts as bars filled up to their `system_info.*_limit` values.
+    fn render_limits(&mut self, f: &mut Frame, area: Rect) {
+        let Some(metrics) = self.latest_metrics() else {
+            return;
+        };
+        let usages = LIMITED_RESOURCES
+            .iter()
+            .filter_map(|kind| {
+                let count = metrics
+                    .items
+                    .get(&format!("system_info.{kind}_count"))?
+                    .as_f64()?;
+                let limit = metrics
+                    .items
+                    .get(&format!("system_info.{kind}_limit"))?
+                    .as_f64()?;
+                Some((*kind, count, limit))
+            })
+            .collect::<Vec<_>>();
+
+        // Each gauge takes a line, and the block borders take two more.
+        let area = popup_area(area, usages.len().max(1) as u16 + 2);
+        f.render_widget(Clear, area);
+        let block = self.make_block("Limits ('g' or ESC to close)");
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        if usages.is_empty() {
+            f.render_widget(Paragraph::new("(no limits are collected)"), inner);
+            return;
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); usages.len()])
+            .split(inner);
+        for ((kind, count, limit), chunk) in usages.into_iter().zip(chunks.iter()) {
+            let ratio = if limit > 0.0 {
+                (count / limit).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let label = format!(
+                "{kind}: {} / {} ({:.1} %)",
+                format_i64(count as i64, ""),
+                format_i64(limit as i64, ""),
+                ratio * 100.0
+            );
+            let gauge = Gauge::default()
+                .gauge_style(self.theme.utilization_style(ratio * 100.0))
+                .ratio(ratio)
+                .label(label);
+            f.render_widget(gauge, *chunk);
+        }
+    }
+
+    /// Renders the lengths of the `statistics.run_queue.NN` run queues as a grid of colored cells, which
+    /// wraps to the width of the popup.
+    ///
+    /// The colors are relative to the longest queue, or to `HEATMAP_MIN_SCALE` if all queues are shorter
+    /// so that a few queued processes don't look alarming.
+    fn render_run_queue_heatmap(&mut self, f: &mut Frame, area: Rect) {
+        let prefix = format!("{RUN_QUEUE_METRIC_NAME}.");
+        let lengths = self
             .latest_metrics()
-            .root_items()
-            .nth(self.metrics_table_state.selected().unwrap_or(0))
-            .expect("unreachable")
-            .0;
-
-        let metric_name = match self.focus {
-            Focus::Main => root_metric_name,
-            Focus::Sub => self
-                .latest_metrics()
-                .child_items(root_metric_name)
-                .nth(self.detail_table_state.selected().unwrap_or(0))
-                .map(|(k, _)| k)
-                .unwrap_or(root_metric_name),
-        };
-
-        let start = self.history[0].timestamp;
-        let mut data = Vec::with_capacity(self.history.len());
-        for metrics in &self.history {
+            .map(|metrics| {
+                metrics
+                    .child_items(RUN_QUEUE_METRIC_NAME)
+                    .filter_map(|(name, value)| {
+                        let index = name.strip_prefix(&prefix)?;
+                        index.parse::<usize>().ok()?;
+                        Some((index, value.as_f64()?))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // Each cell is two lines (the run queue index and its length) of `HEATMAP_CELL_WIDTH` columns.
+        let popup_width = area.width * 3 / 5;
+        let columns = usize::from(popup_width.saturating_sub(2) / HEATMAP_CELL_WIDTH).max(1);
+        let rows = lengths.len().div_ceil(columns);
+        let area = popup_area(area, (rows * 2).max(1) as u16 + 2);
+        f.render_widget(Clear, area);
+        let block = self.make_block("Run queue lengths ('H' or ESC to close)");
+        if lengths.is_empty() {
+            f.render_widget(
+                Paragraph::new("(no run queue lengths are collected)").block(block),
+                area,
+            );
+            return;
+        }
+
+        let scale = lengths
+            .iter()
+            .map(|(_, length)| *length)
+            .fold(HEATMAP_MIN_SCALE, f64::max);
+        let width = usize::from(HEATMAP_CELL_WIDTH) - 1;
+        let mut lines = Vec::with_capacity(rows * 2);
+        for chunk in lengths.chunks(columns) {
+            let mut indices = Vec::new();
+            let mut values = Vec::new();
+            for (index, length) in chunk {
+                let style = self.theme.heat_style(length / scale * 100.0);
+                indices.push(Span::styled(format!("{index:>width$}"), style));
+                indices.push(Span::from(" "));
+                values.push(Span::styled(
+                    format!("{:>width$}", format_i64(*length as i64, "")),
+                    style.add_modifier(Modifier::BOLD),
+                ));
+                values.push(Span::from(" "));
+            }
+            lines.push(Line::from(indices));
+            lines.push(Line::from(values));
+        }
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// Renders the static facts about the node fetched when it was connected.
+    fn render_node_info(&mut self, f: &mut Frame, area: Rect) {
+        let node_info = &self.node().header.node_info;
+        let area = popup_area(area, node_info.len().max(1) as u16 + 2);
+        f.render_widget(Clear, area);
+        let block = self.make_block(&format!(
+            "Node info of {} ('i' or ESC to close)",
+            self.node().header.node_name
+        ));
+        if node_info.is_empty() {
+            f.render_widget(
+                Paragraph::new("(no node info is available)").block(block),
+                area,
+            );
+            return;
+        }
+
+        let rows = node_info
+            .iter()
+            .map(|(label, value)| {
+                Row::new(vec![
+                    Cell::from(label.clone()).style(self.theme.title_style()),
+                    Cell::from(value.clone()),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let table = Table::new(
+            rows,
+            [Constraint::Percentage(35), Constraint::Percentage(65)],
+        )
+        .block(block);
+        f.render_widget(table, area);
+    }
+
+    fn render_prompt(&mut self, f: &mut Frame, area: Rect) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        let (title, action) = match prompt.kind {
+            PromptKind::Node => ("Connect to node (name@host)", "connect"),
+            PromptKind::Marker => ("Add marker (label)", "add"),
+            PromptKind::Threshold => ("Threshold line (empty to remove)", "set"),
+        };
+        let paragraph = Paragraph::new(vec![
+            Line::from(format!("{}_", prompt.input)),
+            Line::from(""),
+            Line::from(format!("Enter: {action}, ESC: cancel")),
+        ])
+        .block(self.make_block(title));
+
+        let area = popup_area(area, 5);
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+
+    fn render_help(&mut self, f: &mut Frame, area: Rect) {
+        let title = if let Some(message) = &self.message {
+            format!("Help ({message})")
+        } else {
+            "Help".to_owned()
+        };
+        // The full list is an overlay so that the help box never takes the rows of the metrics table.
+        let paragraph = Paragraph::new(Line::from("Quit: 'q' key, Keys: 'k' key"))
+            .block(self.make_block(&title))
+            .alignment(Alignment::Left);
+        f.render_widget(paragraph, area);
+    }
+
+    fn selected_series_tsv(&self) -> Option<(String, String)> {
+        let metric_name = self.selected_metric_name()?;
+        let history = &self.node().full_history;
+        let data = self.series_data(metric_name, history)?;
+        let start = history.front()?.timestamp;
+        let mut tsv = String::new();
+        for (x, y) in data {
+            let timestamp = self
+                .node()
+                .header
+                .wall_clock(start + Duration::from_secs_f64(x));
+            tsv += &format!("{}\t{y}\n", format_timestamp(timestamp));
+        }
+        Some((metric_name.to_owned(), tsv))
+    }
+
+    fn selected_metric_name(&self) -> Option<&str> {
+        let root_metric_name = self.selected_root_name()?;
+        match self.focus {
+            Focus::Main | Focus::Panel(_) => Some(root_metric_name),
+            Focus::Sub => Some(
+                self.child_names(root_metric_name)
+                    .into_iter()
+                    .take(self.detail_table_state.selected().unwrap_or(0) + 1)
+                    .next_back()
+                    .unwrap_or(root_metric_name),
+            ),
+        }
+    }
+
+    fn chart_type(&self, metric_name: &str) -> ChartType {
+        self.chart_types
+            .get(metric_name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn chart_data(&self) -> Option<(&str, Vec<(f64, f64)>)> {
+        let metric_name = self.selected_metric_name()?;
+        let data = self.series_data(metric_name, &self.node().history)?;
+        Some((metric_name, data))
+    }
+
+    /// Returns the `(seconds since the first sample, value)` series of the given metric.
+    fn series_data(
+        &self,
+        metric_name: &str,
+        history: &VecDeque<Metrics>,
+    ) -> Option<Vec<(f64, f64)>> {
+        let start = history.front()?.timestamp;
+        let mut data = Vec::with_capacity(history.len());
+        let mut prev = None;
+        for metrics in history {
             let x = (metrics.timestamp - start).as_secs_f64();
-            if let Some(y) = metrics.items.get(metric_name).and_then(|x| x.as_f64()) {
+            let value = metrics.items.get(metric_name);
+            let y = if self.cumulative_metrics.contains(metric_name) {
+                value.and_then(|x| x.raw_value()).map(|x| x as f64)
+            } else {
+                value.and_then(|x| x.as_f64())
+            };
+            let Some(y) = y else {
+                continue;
+            };
+            if !self.delta_metrics.contains(metric_name) {
                 data.push((x, y));
+            } else if let Some((prev_x, prev_y)) = prev.replace((x, y)) {
+                data.push((x, (y - prev_y) / (x - prev_x)));
             }
         }
-        (metric_name, data)
+        Some(data)
     }
 
-    fn render_chart(&mut self, f: &mut Frame, area: Rect) {
-        let (metric_name, data) = self.chart_data();
-        let block = self.make_block(&format!("Chart of {:?}", metric_name));
+    /// Returns the latest first-difference-per-second of the given metric.
+    fn latest_delta(&self, name: &str) -> Option<f64> {
+        let mut samples = self.node().history.iter().rev().filter_map(|metrics| {
+            let value = metrics.items.get(name)?.as_f64()?;
+            Some((metrics.timestamp, value))
+        });
+        let (t1, v1) = samples.next()?;
+        let (t0, v0) = samples.next()?;
+        Some((v1 - v0) / (t1 - t0).as_secs_f64())
+    }
 
-        if data.is_empty() {
-            f.render_widget(block, area);
+    fn render_chart(&self, f: &mut Frame, area: Rect) {
+        let Some((metric_name, data)) = self.chart_data() else {
+            f.render_widget(self.make_block("Chart"), area);
             return;
-        }
+        };
+        self.render_metric_chart(f, area, metric_name, data);
+    }
 
-        let datasets = vec![Dataset::default()
-            .marker(Marker::Braille)
-            .graph_type(GraphType::Line)
-            .data(&data)];
+    fn render_metric_chart(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        metric_name: &str,
+        data: Vec<(f64, f64)>,
+    ) {
+        let chart_type = self.chart_type(metric_name);
+        let mut title = format!("Chart of {:?}", metric_name);
+        if self.delta_metrics.contains(metric_name) {
+            title += " (delta/s)";
+        }
+        if chart_type != ChartType::Line {
+            title += &format!(" [{}]", chart_type.name());
+        }
+        let log_scale = self.log_scale_metrics.contains(metric_name);
+        if log_scale {
+            title += " [log]";
+        }
+        let cumulative = self.cumulative_metrics.contains(metric_name);
+        if cumulative {
+            title += " [cumulative]";
+        }
+        if let Some(threshold) = self.thresholds.get(metric_name) {
+            title += &format!(" [threshold: {threshold}]");
+        }
+        let baseline = self.baselines.get(metric_name);
+        if baseline.is_some() {
+            title += " [baseline]";
+        }
+        let smoothing = self.smoothing_window(metric_name).filter(|_| !cumulative);
+        if let Some(n) = smoothing {
+            title += &format!(" [avg of {n}]");
+        }
+        let mut block = self.make_block(&title);
+
+        // With `--smooth`, the moving average is plotted over the (optional) raw values.
+        let (data, raw_data) = match smoothing {
+            Some(n) => (
+                moving_average(&data, n),
+                self.show_raw_series.then_some(data),
+            ),
+            None => (data, None),
+        };
 
-        let lower_bound = data
-            .iter()
-            .map(|(_, y)| *y)
-            .min_by(|a, b| a.total_cmp(b))
-            .expect("unreachable")
-            .floor();
-        let mut upper_bound = data
+        let ys = data
             .iter()
-            .map(|(_, y)| *y)
-            .max_by(|a, b| a.total_cmp(b))
-            .expect("unreachable")
-            .ceil();
+            .chain(raw_data.iter().flatten())
+            .map(|(_, y)| *y);
+        let (Some(min), Some(max), Some(current)) = (
+            ys.clone().min_by(|a, b| a.total_cmp(b)),
+            ys.max_by(|a, b| a.total_cmp(b)),
+            data.last().map(|(_, y)| *y),
+        ) else {
+            f.render_widget(block, area);
+            return;
+        };
+
+        // Utilization and float gauge bounds are rounded to the displayed decimal places instead of integers.
+        let latest_value = (!self.delta_metrics.contains(metric_name))
+            .then(|| {
+                self.latest_metrics()
+                    .and_then(|metrics| metrics.items.get(metric_name))
+            })
+            .flatten();
+        let is_utilization = matches!(latest_value, Some(MetricValue::Utilization { .. }));
+        let is_float = matches!(latest_value, Some(MetricValue::FloatGauge { .. }));
+        let step = if is_utilization {
+            10f64.powi(-(utilization_decimals() as i32))
+        } else if is_float {
+            10f64.powi(-(float_decimals() as i32))
+        } else {
+            1.0
+        };
+        // The bounds are widened to include the threshold line and the baseline even if the data is far from them.
+        let threshold = self.thresholds.get(metric_name).copied();
+        let (bound_min, bound_max) = threshold
+            .into_iter()
+            .chain(
+                baseline
+                    .iter()
+                    .flat_map(|data| data.iter().map(|(_, y)| *y)),
+            )
+            .fold((min, max), |(min, max), y| (min.min(y), max.max(y)));
+        let mut lower_bound = (bound_min / step).floor() * step;
+        let mut upper_bound = (bound_max / step).ceil() * step;
         let is_constant = lower_bound == upper_bound;
         if is_constant {
-            upper_bound = lower_bound + 1.0;
+            upper_bound = lower_bound + step;
         }
+        let format_bound = |bound: f64| {
+            if is_utilization {
+                format_utilization(bound)
+            } else if is_float {
+                format_f64(bound, "")
+            } else {
+                format_axis_value(metric_name, bound, "")
+            }
+        };
 
-        let y_labels = if is_constant {
-            vec![
-                Span::from(format_u64(lower_bound as u64, "")),
-                Span::from(""),
-            ]
+        // Exact values of the plotted series, shortened or omitted if the chart is too narrow.
+        let (now, min, max) = (format_bound(current), format_bound(min), format_bound(max));
+        let readout = [
+            format!(" now: {now}, min: {min}, max: {max} "),
+            format!(" {now} ({min} .. {max}) "),
+            format!(" {now} "),
+        ]
+        .into_iter()
+        .find(|readout| readout.chars().count() + 2 <= usize::from(area.width));
+        if let Some(readout) = readout {
+            block = block.title_bottom(Line::from(readout).alignment(Alignment::Right));
+        }
+
+        // On a log scale, the data is plotted as log10 values with bounds at powers of ten.
+        // Non-positive values have no logarithm and are skipped.
+        let mut data = data;
+        let mut raw_data = raw_data;
+        let mut threshold = threshold;
+        let mut baseline = baseline.cloned();
+        if log_scale {
+            data = log10_series(data);
+            raw_data = raw_data.map(log10_series);
+            threshold = threshold.filter(|t| *t > 0.0).map(f64::log10);
+            baseline = baseline.map(log10_series);
+            let ys = data
+                .iter()
+                .chain(raw_data.iter().flatten())
+                .chain(baseline.iter().flatten())
+                .map(|(_, y)| *y)
+                .chain(threshold);
+            let (Some(min), Some(max)) = (
+                ys.clone().min_by(|a, b| a.total_cmp(b)),
+                ys.max_by(|a, b| a.total_cmp(b)),
+            ) else {
+                f.render_widget(block, area);
+                return;
+            };
+            lower_bound = min.floor();
+            upper_bound = max.ceil().max(lower_bound + 1.0);
+        }
+
+        let data = match chart_type {
+            ChartType::Area => {
+                // A braille cell is four dots high.
+                let step = (upper_bound - lower_bound) / (f64::from(area.height) * 4.0);
+                fill_area(&data, lower_bound, step)
+            }
+            _ => data,
+        };
+        let graph_type = match chart_type {
+            ChartType::Line => GraphType::Line,
+            ChartType::Scatter | ChartType::Area => GraphType::Scatter,
+        };
+        let marker_lines = self.marker_lines(lower_bound, upper_bound);
+        let threshold_line = threshold.map(|t| [(0.0, t), (CHART_DURATION as f64, t)]);
+        let mut datasets = Vec::new();
+        if let Some(baseline) = &baseline {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(self.theme.baseline_style())
+                    .data(baseline),
+            );
+        }
+        if let Some(raw_data) = &raw_data {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(self.series_style(metric_name).add_modifier(Modifier::DIM))
+                    .data(raw_data),
+            );
+        }
+        let style = if smoothing.is_some() {
+            self.series_style(metric_name).add_modifier(Modifier::BOLD)
+        } else {
+            self.series_style(metric_name)
+        };
+        datasets.push(
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(graph_type)
+                .style(style)
+                .data(&data),
+        );
+        if let Some(line) = &threshold_line {
+            datasets.push(
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(self.theme.threshold_style())
+                    .data(line),
+            );
+        }
+        for (label, line) in &marker_lines {
+            datasets.push(
+                Dataset::default()
+                    .name(label.as_str())
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(self.theme.marker_style())
+                    .data(line),
+            );
+        }
+
+        let y_labels = if log_scale {
+            // One label per power of ten if they fit (ratatui spaces labels evenly).
+            let decades = (upper_bound - lower_bound) as usize;
+            let exponents = if decades < usize::from(area.height.saturating_sub(3)) {
+                (0..=decades).map(|i| lower_bound + i as f64).collect()
+            } else {
+                vec![lower_bound, upper_bound]
+            };
+            exponents
+                .into_iter()
+                .map(|exponent| Span::from(format_bound(10f64.powf(exponent))))
+                .collect()
+        } else if is_constant {
+            vec![Span::from(format_bound(lower_bound)), Span::from("")]
         } else {
             vec![
-                Span::from(format_u64(lower_bound as u64, "")),
-                Span::from(format_u64(upper_bound as u64, "")),
+                Span::from(format_bound(lower_bound)),
+                Span::from(format_bound(upper_bound)),
             ]
         };
 
@@ -518,149 +1900,1353 @@ impl UiState {
         f.render_widget(chart, area);
     }
 
-    fn collect_detailed_items(&self) -> (&str, Vec<(&str, &MetricValue)>) {
-        let root_name = self
-            .latest_metrics()
-            .root_items()
-            .nth(self.metrics_table_state.selected().unwrap_or(0))
-            .expect("unreachable")
-            .0;
-        let children = self.latest_metrics().child_items(root_name).collect();
-        (root_name, children)
+    /// Returns the vertical lines of the markers in the chart window.
+    fn marker_lines(&self, lower_bound: f64, upper_bound: f64) -> Vec<(String, [(f64, f64); 2])> {
+        let Some(start) = self.node().history.front().map(|m| m.timestamp) else {
+            return Vec::new();
+        };
+        let end = start + Duration::from_secs(CHART_DURATION);
+        self.markers
+            .iter()
+            .filter(|m| start <= m.timestamp && m.timestamp <= end)
+            .map(|m| {
+                let x = (m.timestamp - start).as_secs_f64();
+                (m.marker.clone(), [(x, lower_bound), (x, upper_bound)])
+            })
+            .collect()
     }
 
-    fn render_detail(&mut self, f: &mut Frame, area: Rect) {
-        let (root_metric_name, items) = self.collect_detailed_items();
-        let block = self.make_block(&format!("Detail of {:?}", root_metric_name));
-
-        let header_cells = ["Name", "Value", "Avg (1m)"]
-            .into_iter()
-            .map(|h| Cell::from(h).style(Style::default().add_modifier(Modifier::BOLD)));
-        let header = Row::new(header_cells).bottom_margin(1);
+    fn collect_detailed_items(&self) -> Option<(&str, Vec<(&str, &MetricValue)>)> {
+        let root_name = self.selected_root_name()?;
+        let children = self
+            .latest_metrics()?
+            .child_items(root_name)
+            .filter(|(name, _)| !self.is_hidden_zero(name))
+            .collect();
+        Some((root_name, children))
+    }
 
-        let is_avg_available = self.start.elapsed().as_secs() >= ONE_MINUTE;
-        let mut value_width = 0;
-        let mut avg_width = 0;
-        let mut row_items = Vec::with_capacity(items.len());
-        for (name, item) in &items {
-            let value = item.to_string();
-            let avg = if is_avg_available {
-                self.averages
-                    .get(*name)
-                    .map(|v| v.get().to_string())
-                    .unwrap_or("".to_string())
-            } else {
-                "".to_string()
-            };
-            value_width = std::cmp::max(value_width, value.len());
-            avg_width = std::cmp::max(avg_width, avg.len());
-            row_items.push((name.to_string(), value, avg));
-        }
+    fn render_detail(&mut self, f: &mut Frame, area: Rect) {
+        let thread_utilizations = self.thread_utilizations();
+        let area = if thread_utilizations.is_empty() {
+            area
+        } else {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(8)].as_ref())
+                .split(area);
+            self.render_thread_bars(f, chunks[1], &thread_utilizations);
+            chunks[0]
+        };
 
-        let rows = row_items.into_iter().map(|(name, value, avg)| {
-            Row::new(vec![
-                Cell::from(name),
-                Cell::from(format!("{:>value_width$}", value)),
-                Cell::from(format!("{:>avg_width$}", avg)),
-            ])
-        });
+        let row_count = match self.selected_root_name() {
+            Some(root_name) if self.compare => self.child_names(root_name).len(),
+            _ => self
+                .collect_detailed_items()
+                .map_or(0, |(_, items)| items.len()),
+        };
+        let selected = std::cmp::min(
+            self.detail_table_state.selected().unwrap_or(0),
+            row_count.saturating_sub(1),
+        );
+        self.detail_table_state.select(Some(selected));
+        let window = self.row_window(Focus::Sub, row_count, selected, area);
+
+        let (block, items) = match self.collect_detailed_items() {
+            Some((root_metric_name, mut items)) => {
+                items.truncate(window.end);
+                items.drain(..window.start);
+                (
+                    self.make_block(&format!("Detail of {:?}", root_metric_name)),
+                    items,
+                )
+            }
+            None => (self.make_block("Detail"), Vec::new()),
+        };
 
-        let widths = [
-            Constraint::Percentage(50),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-        ];
+        let is_avg_available = self.is_avg_available();
+        let (header, mut rows, widths) = match self.selected_root_name() {
+            Some(root_name) if self.compare => {
+                let names = self.child_names(root_name);
+                self.make_compare_table_contents(Focus::Sub, &names[window.clone()])
+            }
+            _ => {
+                let total = self
+                    .selected_root_name()
+                    .filter(|name| name.ends_with("_bytes"))
+                    .and_then(|name| self.latest_metrics()?.items.get(name))
+                    .filter(|value| matches!(value, MetricValue::Gauge { .. }))
+                    .and_then(|value| value.as_f64());
+                self.make_table_contents(Focus::Sub, &items, is_avg_available, total)
+            }
+        };
+        rows.extend(self.overflow_row(row_count, &window));
 
         let highlight_style = if self.focus == Focus::Sub {
-            Style::default().add_modifier(Modifier::REVERSED)
+            self.theme.highlight_style()
         } else {
             Style::default()
         };
         let highlight_symbol = if self.focus == Focus::Sub { "> " } else { "  " };
 
-        let selected = std::cmp::min(
-            self.detail_table_state.selected().unwrap_or(0),
-            items.len().saturating_sub(1),
-        );
-        self.detail_table_state.select(Some(selected));
-
         let table = Table::new(rows, widths)
             .header(header)
             .block(block)
             .highlight_style(highlight_style)
             .highlight_symbol(highlight_symbol);
-        f.render_stateful_widget(table, area, &mut self.detail_table_state);
+        if window.len() == row_count {
+            f.render_stateful_widget(table, area, &mut self.detail_table_state);
+        } else {
+            let mut state = TableState::default().with_selected(Some(selected - window.start));
+            f.render_stateful_widget(table, area, &mut state);
+        }
     }
 
-    fn make_block(&self, name: &str) -> Block<'static> {
-        Block::default().borders(Borders::ALL).title(Span::styled(
-            name.to_string(),
-            Style::default().add_modifier(Modifier::BOLD),
-        ))
+    /// Returns the per-thread utilizations (in percent) of the selected `utilization.*` root metric.
+    ///
+    /// Unlike the detail table, idle threads are included even if zero values are hidden.
+    fn thread_utilizations(&self) -> Vec<(String, u64)> {
+        let Some((root_name, metrics)) = self.selected_root_name().zip(self.latest_metrics())
+        else {
+            return Vec::new();
+        };
+        if !root_name.starts_with("utilization.") {
+            return Vec::new();
+        }
+        let prefix = format!("{root_name}.thread.");
+        metrics
+            .child_items(root_name)
+            .filter_map(|(name, value)| {
+                let thread_id = name.strip_prefix(&prefix)?;
+                Some((thread_id.to_owned(), value.as_f64()?.round() as u64))
+            })
+            .collect()
     }
 
-    fn latest_metrics(&self) -> &Metrics {
-        self.history.back().expect("unreachable")
+    fn render_thread_bars(&self, f: &mut Frame, area: Rect, thread_utilizations: &[(String, u64)]) {
+        let bars = thread_utilizations
+            .iter()
+            .map(|(thread_id, utilization)| {
+                Bar::default()
+                    .value(*utilization)
+                    .label(Line::from(thread_id.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        // Fit all bars into the panel, leaving a one-cell gap between bars if there is room.
+        let n = thread_utilizations.len() as u16;
+        let inner_width = area.width.saturating_sub(2);
+        let bar_gap = if n * 2 <= inner_width { 1 } else { 0 };
+        let bar_width = (inner_width / n).saturating_sub(bar_gap).clamp(1, 5);
+
+        let chart = BarChart::default()
+            .block(self.make_block("Utilization per thread (%)"))
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(bar_gap)
+            .max(100);
+        f.render_widget(chart, area);
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Focus {
-    Main,
-    Sub,
-}
+    /// Makes a table of the given metrics.
+    ///
+    /// If `total` is given, a "% of total" column showing the share of each value in it is appended.
+    fn make_table_contents(
+        &self,
+        table: Focus,
+        items: &[(&str, &MetricValue)],
+        is_avg_available: bool,
+        total: Option<f64>,
+    ) -> (Row<'static>, Vec<Row<'static>>, Vec<Constraint>) {
+        let share_title = total.map(|_| SHARE_COLUMN_TITLE);
+        let header_cells = self
+            .columns
+            .iter()
+            .map(|c| c.title())
+            .chain(share_title)
+            .map(|title| Cell::from(title).style(self.theme.title_style()));
+        let header = Row::new(header_cells).bottom_margin(1);
 
-#[derive(Debug, Clone)]
-struct AvgValue {
-    sum: MetricValue,
-    cnt: usize,
-}
+        let binary_growing = self.is_steadily_growing(BINARY_MEMORY_METRIC_NAME);
+        let mut column_widths = vec![0; self.columns.len() + usize::from(total.is_some())];
+        let mut row_items = Vec::with_capacity(items.len());
+        for (name, item) in items {
+            let share = total.map(|total| match item.as_f64() {
+                Some(value) if total > 0.0 => format!("{:.1} %", value / total * 100.0),
+                _ => String::new(),
+            });
+            let row = self
+                .columns
+                .iter()
+                .map(|column| match column {
+                    Column::Name if self.delta_metrics.contains(*name) => {
+                        format!("{name} (delta/s)")
+                    }
+                    Column::Name if self.smoothing_window(name).is_some() => {
+                        format!("{name} (smoothed)")
+                    }
+                    Column::Name if *name == BINARY_MEMORY_METRIC_NAME && binary_growing => {
+                        format!("{name} (growing)")
+                    }
+                    Column::Name if *name == TOTAL_MEMORY_METRIC_NAME && binary_growing => {
+                        format!("{name} (binary growing)")
+                    }
+                    Column::Name => name.to_string(),
+                    Column::Value if self.delta_metrics.contains(*name) => self
+                        .latest_delta(name)
+                        .map(|v| format_axis_value(name, v, "/s"))
+                        .unwrap_or_default(),
+                    Column::Value => self
+                        .smoothed_rate(name)
+                        .map_or_else(|| item.format(name), |v| v.format(name)),
+                    Column::Avg if is_avg_available => self
+                        .window_avg(name)
+                        .map(|v| v.format(name))
+                        .unwrap_or_default(),
+                    Column::Avg => "".to_string(),
+                    Column::Min => self
+                        .window_min_max(name)
+                        .map(|(min, _)| min.format(name))
+                        .unwrap_or_default(),
+                    Column::Max => self
+                        .window_min_max(name)
+                        .map(|(_, max)| max.format(name))
+                        .unwrap_or_default(),
+                    Column::Raw => match item.raw_value() {
+                        Some(raw) if is_bytes_metric(name) => format_bytes(raw, ""),
+                        Some(raw) if is_cpu_time_metric(name) => {
+                            format_micros_as_secs(raw as f64, "")
+                        }
+                        Some(raw) => format_u64(raw, ""),
+                        None => String::new(),
+                    },
+                })
+                .chain(share)
+                .collect::<Vec<_>>();
+            for (width, cell) in column_widths.iter_mut().zip(&row) {
+                *width = std::cmp::max(*width, cell.len());
+            }
+            row_items.push((self.series_style(name), row));
+        }
+        self.stick_widths(table, &mut column_widths);
 
-impl AvgValue {
-    fn new(value: MetricValue) -> Self {
-        Self { sum: value, cnt: 1 }
-    }
+        let is_name_column = self
+            .columns
+            .iter()
+            .map(|column| *column == Column::Name)
+            .chain(total.map(|_| false))
+            .collect::<Vec<_>>();
+        let rows = row_items
+            .into_iter()
+            .map(|(style, row)| {
+                Row::new(
+                    row.into_iter()
+                        .zip(&is_name_column)
+                        .zip(&column_widths)
+                        .map(|((cell, is_name), width)| {
+                            if *is_name {
+                                Cell::from(cell).style(style)
+                            } else {
+                                Cell::from(format!("{:>width$}", cell))
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        let stats_columns = (self.columns.iter().filter(|c| **c != Column::Name).count()
+            + usize::from(total.is_some())) as u32;
+        let name_percentage = if stats_columns == 0 {
+            100
+        } else if self.columns.contains(&Column::Name) {
+            50
+        } else {
+            0
+        };
+        let widths = is_name_column
+            .iter()
+            .map(|is_name| {
+                if *is_name {
+                    Constraint::Percentage(name_percentage)
+                } else {
+                    Constraint::Ratio(100 - u32::from(name_percentage), 100 * stats_columns)
+                }
+            })
+            .collect();
 
-    fn add(&mut self, v: MetricValue) {
-        self.sum += v;
-        self.cnt += 1;
+        (header, rows, widths)
     }
 
-    fn sub(&mut self, v: MetricValue) {
-        self.sum -= v;
-        self.cnt -= 1;
+    /// Widens `widths` to the widest ones seen so far in `table` (unless `--no-sticky-widths` is specified).
+    fn stick_widths(&self, table: Focus, widths: &mut [usize]) {
+        let Some(sticky_widths) = &self.sticky_widths else {
+            return;
+        };
+        let mut sticky_widths = sticky_widths.borrow_mut();
+        let sticky = sticky_widths.entry(table).or_default();
+        sticky.resize(widths.len(), 0);
+        for (sticky, width) in sticky.iter_mut().zip(widths.iter_mut()) {
+            *sticky = std::cmp::max(*sticky, *width);
+            *width = *sticky;
+        }
     }
 
-    fn get(&self) -> MetricValue {
-        match self.sum {
-            MetricValue::Gauge { value, .. } => {
-                let value = (value as f64 / self.cnt as f64).round() as u64;
-                MetricValue::Gauge {
-                    value,
-                    parent: None,
-                }
-            }
-            MetricValue::Counter {
-                value: Some(value), ..
-            } => {
-                let value = value / self.cnt as f64;
-                MetricValue::Counter {
-                    raw_value: 0,
-                    value: Some(value),
-                    parent: None,
+    /// Makes a table of the latest values of both record files and their differences.
+    fn make_compare_table_contents(
+        &self,
+        table: Focus,
+        names: &[&str],
+    ) -> (Row<'static>, Vec<Row<'static>>, Vec<Constraint>) {
+        let header_cells = ["Name", "A", "B", "B - A"]
+            .into_iter()
+            .map(|title| Cell::from(title).style(self.theme.title_style()));
+        let header = Row::new(header_cells).bottom_margin(1);
+
+        let mut row_items = Vec::with_capacity(names.len());
+        for name in names {
+            let [a, b] = [0, 1].map(|i| {
+                self.nodes[i]
+                    .history
+                    .back()
+                    .and_then(|metrics| metrics.items.get(*name))
+            });
+            let diff = match (a.and_then(|a| a.as_f64()), b.and_then(|b| b.as_f64())) {
+                (Some(a_value), Some(b_value)) => {
+                    let is_float = matches!(b, Some(MetricValue::FloatGauge { .. }));
+                    format_diff(name, b_value - a_value, is_float)
                 }
+                _ => String::new(),
+            };
+            // Metrics present in only one of the record files.
+            let missing = || "(missing)".to_owned();
+            row_items.push([
+                name.to_string(),
+                a.map(|a| a.format(name)).unwrap_or_else(missing),
+                b.map(|b| b.format(name)).unwrap_or_else(missing),
+                diff,
+            ]);
+        }
+
+        let mut column_widths = [0; 4];
+        for row in &row_items {
+            for (width, cell) in column_widths.iter_mut().zip(row) {
+                *width = std::cmp::max(*width, cell.len());
             }
-            MetricValue::Counter { .. } => MetricValue::Counter {
+        }
+        self.stick_widths(table, &mut column_widths);
+        let rows = row_items
+            .into_iter()
+            .map(|row| {
+                Row::new(
+                    row.into_iter()
+                        .zip(column_widths)
+                        .enumerate()
+                        .map(|(i, (cell, width))| {
+                            if i == 0 {
+                                Cell::from(cell)
+                            } else {
+                                Cell::from(format!("{:>width$}", cell))
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        let widths = vec![
+            Constraint::Percentage(40),
+            Constraint::Percentage(20),
+            Constraint::Percentage(20),
+            Constraint::Percentage(20),
+        ];
+        (header, rows, widths)
+    }
+
+    /// Returns the average of the given metric over the samples in the history.
+    ///
+    /// This is recomputed from the samples on each call so that the result stays correct
+    /// even if the metric appears or disappears in the middle of the window.
+    fn window_avg(&self, name: &str) -> Option<MetricValue> {
+        let mut latest = None;
+        let mut sum = 0.0;
+        let mut count = 0;
+        for value in self
+            .node()
+            .history
+            .iter()
+            .filter_map(|metrics| metrics.items.get(name))
+        {
+            if let Some(v) = value.as_f64() {
+                sum += v;
+                count += 1;
+                latest = Some(value);
+            }
+        }
+        let avg = sum / count as f64;
+        let value = match latest? {
+            MetricValue::Gauge { .. } => MetricValue::Gauge {
+                value: avg.round() as i64,
+                parent: None,
+            },
+            MetricValue::Counter { .. } => MetricValue::Counter {
                 raw_value: 0,
-                value: None,
+                value: Some(avg),
+                parent: None,
+            },
+            MetricValue::Utilization { .. } => MetricValue::Utilization {
+                value: avg,
+                parent: None,
+            },
+            MetricValue::FloatGauge { .. } => MetricValue::FloatGauge {
+                value: avg,
                 parent: None,
             },
-            MetricValue::Utilization { value, .. } => {
-                let value = value / self.cnt as f64;
-                MetricValue::Utilization {
-                    value,
-                    parent: None,
+        };
+        Some(value)
+    }
+
+    /// Returns `true` if the given metric never decreased and has grown over the window.
+    ///
+    /// A steadily climbing `memory.binary_bytes` is a typical sign of a binary leak.
+    fn is_steadily_growing(&self, name: &str) -> bool {
+        let values = self
+            .node()
+            .history
+            .iter()
+            .filter_map(|metrics| metrics.items.get(name)?.as_f64())
+            .collect::<Vec<_>>();
+        values.len() >= MIN_GROWTH_SAMPLES
+            && values.windows(2).all(|w| w[0] <= w[1])
+            && values.first() < values.last()
+    }
+
+    /// Returns the `--smooth` window if the given metric is a counter whose rate is smoothed.
+    fn smoothing_window(&self, name: &str) -> Option<usize> {
+        let n = self.smooth?;
+        let is_counter = matches!(
+            self.latest_metrics()
+                .and_then(|metrics| metrics.items.get(name)),
+            Some(MetricValue::Counter { .. })
+        );
+        (is_counter && !self.delta_metrics.contains(name)).then_some(n)
+    }
+
+    /// Returns the moving average of the latest rates of the given counter (see `--smooth`).
+    ///
+    /// Like `window_avg()`, this is recomputed from the samples in the history on each call.
+    fn smoothed_rate(&self, name: &str) -> Option<MetricValue> {
+        let n = self.smoothing_window(name)?;
+        let rates = self
+            .node()
+            .history
+            .iter()
+            .rev()
+            .filter_map(|metrics| metrics.items.get(name)?.as_f64())
+            .take(n)
+            .collect::<Vec<_>>();
+        if rates.is_empty() {
+            return None;
+        }
+        Some(MetricValue::Counter {
+            raw_value: 0,
+            value: Some(rates.iter().sum::<f64>() / rates.len() as f64),
+            parent: None,
+        })
+    }
+
+    fn window_min_max(&self, name: &str) -> Option<(&MetricValue, &MetricValue)> {
+        let values = self
+            .node()
+            .history
+            .iter()
+            .filter_map(|metrics| metrics.items.get(name))
+            .filter_map(|value| value.as_f64().map(|v| (v, value)));
+        let min = values.clone().min_by(|(a, _), (b, _)| a.total_cmp(b))?.1;
+        let max = values.max_by(|(a, _), (b, _)| a.total_cmp(b))?.1;
+        Some((min, max))
+    }
+
+    fn make_block(&self, name: &str) -> Block<'static> {
+        Block::default()
+            .borders(Borders::ALL)
+            .title(Span::styled(name.to_string(), self.theme.title_style()))
+    }
+
+    /// Sets `elapsed` to the offset of the latest metrics of the displayed node from the start of its run.
+    ///
+    /// The header time is derived from it, so it always matches the time at which the shown metrics were
+    /// collected (or recorded, in replay mode).
+    fn sync_elapsed(&mut self) {
+        self.elapsed = self
+            .node()
+            .history
+            .back()
+            .map(|metrics| metrics.timestamp)
+            .unwrap_or_default();
+    }
+
+    /// Returns whether a full minute of metrics has been collected since the start of the averaging window.
+    fn is_avg_available(&self) -> bool {
+        self.elapsed
+            .saturating_sub(self.node().window_start)
+            .as_secs()
+            >= (ONE_MINUTE - 1)
+    }
+
+    /// Returns the wall-clock time shown in the header, at which the shown metrics were collected.
+    fn header_time(&self) -> chrono::DateTime<chrono::Local> {
+        self.node().header.wall_clock(self.elapsed)
+    }
+
+    fn node(&self) -> &NodeState {
+        &self.nodes[self.node_index]
+    }
+
+    fn latest_metrics(&self) -> Option<&Metrics> {
+        self.node().history.back()
+    }
+
+    /// Returns the name of the selected root metric.
+    ///
+    /// If the selection is out of range (e.g., some metrics are no longer reported), the last metric is returned.
+    fn selected_root_name(&self) -> Option<&str> {
+        let selected = self.metrics_table_state.selected().unwrap_or(0);
+        self.root_names().into_iter().take(selected + 1).next_back()
+    }
+
+    /// Returns the names of the root metrics (of both record files in compare mode) in the table order.
+    fn root_names(&self) -> Vec<&str> {
+        let mut names = self
+            .compared_metrics()
+            .flat_map(|metrics| metrics.root_items().map(|(name, _)| name))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|name| !self.is_hidden_zero(name))
+            .collect::<Vec<_>>();
+        if self.sort_order != SortOrder::Name {
+            let mut keyed = names
+                .into_iter()
+                .map(|name| {
+                    let key = match self.sort_order {
+                        SortOrder::Name => None,
+                        SortOrder::Value => self
+                            .latest_metrics()
+                            .and_then(|m| m.items.get(name)?.as_f64()),
+                        SortOrder::Avg => self.window_avg(name).and_then(|v| v.as_f64()),
+                    };
+                    (name, key)
+                })
+                .collect::<Vec<_>>();
+
+            // Descending order, with metrics without a value at the end (the sort is stable).
+            keyed.sort_by(|(_, a), (_, b)| match (a, b) {
+                (Some(a), Some(b)) => b.total_cmp(a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+            names = keyed.into_iter().map(|(name, _)| name).collect();
+        }
+        names
+    }
+
+    /// Runs `f` and then selects the root metric that was selected before, which may have moved.
+    fn keep_selection<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let selected = self.selected_root_name().map(|x| x.to_owned());
+        let result = f(self);
+        if let Some(i) = selected.and_then(|name| self.root_names().iter().position(|x| *x == name))
+        {
+            self.metrics_table_state.select(Some(i));
+        }
+        result
+    }
+
+    /// Assigns the next `PALETTE` color to each `utilization.*` root metric seen for the first time.
+    fn assign_utilization_colors(&mut self) {
+        let new_names = self
+            .root_names()
+            .into_iter()
+            .filter(|name| {
+                name.starts_with("utilization.") && !self.utilization_colors.contains_key(*name)
+            })
+            .map(|name| name.to_owned())
+            .collect::<Vec<_>>();
+        for name in new_names {
+            let index = self.utilization_colors.len() % PALETTE.len();
+            self.utilization_colors.insert(name, index);
+        }
+    }
+
+    /// Returns the style of the given metric (or its children) if it is a `utilization.*` metric
+    /// that has been assigned a color.
+    fn series_style(&self, name: &str) -> Style {
+        self.utilization_colors
+            .iter()
+            .find(|(root_name, _)| {
+                name.strip_prefix(root_name.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map_or_else(Style::default, |(_, index)| {
+                self.theme.palette_style(*index)
+            })
+    }
+
+    /// Returns the names of the children of the given metric (of both record files in compare mode).
+    fn child_names<'a>(&'a self, root_name: &'a str) -> Vec<&'a str> {
+        self.compared_metrics()
+            .flat_map(|metrics| metrics.child_items(root_name).map(|(name, _)| name))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|name| !self.is_hidden_zero(name))
+            .collect()
+    }
+
+    /// Returns `true` if zero values are hidden (the 'z' key) and the latest value of the given metric
+    /// is zero (in both record files in compare mode).
+    fn is_hidden_zero(&self, name: &str) -> bool {
+        self.hide_zero
+            && self.compared_metrics().all(|metrics| {
+                metrics
+                    .items
+                    .get(name)
+                    .is_none_or(|value| value.as_f64() == Some(0.0))
+            })
+    }
+
+    /// Returns the latest metrics of the displayed node, or of both record files in compare mode.
+    fn compared_metrics(&self) -> impl Iterator<Item = &Metrics> {
+        let nodes = if self.compare {
+            &self.nodes[..]
+        } else {
+            std::slice::from_ref(self.node())
+        };
+        nodes.iter().filter_map(|node| node.history.back())
+    }
+}
+
+/// Returns a centered area with the given height for a popup.
+fn popup_area(area: Rect, height: u16) -> Rect {
+    let width = area.width * 3 / 5;
+    let height = std::cmp::min(area.height, height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Formats the difference between the values of the metric `name` with an explicit sign.
+fn format_diff(name: &str, diff: f64, is_float: bool) -> String {
+    let sign = if diff < 0.0 { "-" } else { "+" };
+    if name.starts_with("utilization.") {
+        format!("{sign}{}", format_utilization(diff.abs()))
+    } else if is_float {
+        format!("{sign}{}", format_f64(diff.abs(), ""))
+    } else {
+        format!("{sign}{}", format_axis_value(name, diff.abs(), ""))
+    }
+}
+
+/// Formats a chart or delta value of the metric `name`, using byte or second units if appropriate.
+fn format_axis_value(name: &str, value: f64, suffix: &str) -> String {
+    if is_bytes_metric(name) && value >= 0.0 {
+        format_bytes(value.round() as u64, suffix)
+    } else if is_cpu_time_metric(name) {
+        format_micros_as_secs(value, suffix)
+    } else {
+        format_i64(value.round() as i64, suffix)
+    }
+}
+
+fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    if days > 0 {
+        format!("{days}d {}h {}m", hours % 24, minutes % 60)
+    } else if hours > 0 {
+        format!("{hours}h {}m", minutes % 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// How the series of a metric is drawn in the chart panel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ChartType {
+    #[default]
+    Line,
+    Scatter,
+
+    /// The region between the lower bound and the series is filled.
+    Area,
+}
+
+impl ChartType {
+    fn next(self) -> Self {
+        match self {
+            Self::Line => Self::Scatter,
+            Self::Scatter => Self::Area,
+            Self::Area => Self::Line,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Line => "line",
+            Self::Scatter => "scatter",
+            Self::Area => "area",
+        }
+    }
+}
+
+/// Converts the values of a series to log10, skipping non-positive values that have no logarithm.
+fn log10_series(data: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    data.into_iter()
+        .filter(|(_, y)| *y > 0.0)
+        .map(|(x, y)| (x, y.log10()))
+        .collect()
+}
+
+/// Returns the simple moving average of the last (up to) `n` samples at each point.
+fn moving_average(data: &[(f64, f64)], n: usize) -> Vec<(f64, f64)> {
+    let mut sum = 0.0;
+    data.iter()
+        .enumerate()
+        .map(|(i, (x, y))| {
+            sum += y;
+            if i >= n {
+                sum -= data[i - n].1;
+            }
+            (*x, sum / (i + 1).min(n) as f64)
+        })
+        .collect()
+}
+
+/// Approximates a filled area chart by adding points below each sample down to `lower_bound`.
+fn fill_area(data: &[(f64, f64)], lower_bound: f64, step: f64) -> Vec<(f64, f64)> {
+    let mut points = Vec::new();
+    for &(x, y) in data {
+        let mut fill_y = y;
+        while fill_y > lower_bound {
+            points.push((x, fill_y));
+            fill_y -= step;
+        }
+        points.push((x, lower_bound));
+    }
+    points
+}
+
+/// Order of the root metrics in the metrics table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Name,
+
+    /// Latest value (descending).
+    Value,
+
+    /// Average over the window (descending).
+    Avg,
+}
+
+impl SortOrder {
+    fn next(self) -> Self {
+        match self {
+            Self::Name => Self::Value,
+            Self::Value => Self::Avg,
+            Self::Avg => Self::Name,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Value => "value",
+            Self::Avg => "avg",
+        }
+    }
+}
+
+/// Single-line text input shown as a popup.
+#[derive(Debug)]
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            input: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    /// Name of the node to connect to.
+    Node,
+
+    /// Label of the marker to add to the record file.
+    Marker,
+
+    /// Y-value of the threshold line drawn on the chart of the selected metric.
+    Threshold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayoutMode {
+    /// Metrics, help, detail and chart panels.
+    Full,
+
+    /// Only the metrics and help panels.
+    Compact,
+
+    /// Panels defined by `--dashboard`.
+    Dashboard,
+}
+
+/// Color theme of the dashboard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Theme {
+    /// Colors for terminals with a dark background.
+    #[default]
+    Dark,
+
+    /// Colors for terminals with a light background.
+    Light,
+
+    /// No colors (only text attributes such as bold are used).
+    Mono,
+}
+
+impl Theme {
+    fn title_style(self) -> Style {
+        Style::default().add_modifier(Modifier::BOLD)
+    }
+
+    fn highlight_style(self) -> Style {
+        match self {
+            Self::Dark | Self::Mono => Style::default().add_modifier(Modifier::REVERSED),
+            Self::Light => Style::default().fg(Color::Black).bg(Color::Gray),
+        }
+    }
+
+    fn marker_style(self) -> Style {
+        match self {
+            Self::Dark => Style::default().fg(Color::Cyan),
+            Self::Light => Style::default().fg(Color::Magenta),
+            Self::Mono => Style::default(),
+        }
+    }
+
+    fn threshold_style(self) -> Style {
+        match self {
+            Self::Dark | Self::Light => Style::default().fg(Color::Red),
+            Self::Mono => Style::default().add_modifier(Modifier::DIM),
+        }
+    }
+
+    fn palette_style(self, index: usize) -> Style {
+        match self {
+            Self::Dark | Self::Light => Style::default().fg(PALETTE[index % PALETTE.len()]),
+            Self::Mono => Style::default(),
+        }
+    }
+
+    fn baseline_style(self) -> Style {
+        match self {
+            Self::Dark | Self::Light => {
+                Style::default().fg(Color::Gray).add_modifier(Modifier::DIM)
+            }
+            Self::Mono => Style::default().add_modifier(Modifier::DIM),
+        }
+    }
+
+    /// Returns the style of a heatmap cell whose value is `percent` % of the scale.
+    fn heat_style(self, percent: f64) -> Style {
+        let color = if percent >= 75.0 {
+            Color::Red
+        } else if percent >= 50.0 {
+            Color::LightRed
+        } else if percent >= 25.0 {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        match self {
+            Self::Dark | Self::Light => Style::default().fg(Color::Black).bg(color),
+            Self::Mono if percent >= 50.0 => Style::default().add_modifier(Modifier::REVERSED),
+            Self::Mono => Style::default(),
+        }
+    }
+
+    fn utilization_style(self, utilization: f64) -> Style {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        let (high, middle, low) = match self {
+            Self::Dark => (Color::Red, Color::Yellow, Color::Green),
+            Self::Light => (Color::Red, Color::Magenta, Color::Blue),
+            Self::Mono => return style,
+        };
+        if utilization >= 80.0 {
+            style.fg(high)
+        } else if utilization >= 50.0 {
+            style.fg(middle)
+        } else {
+            style.fg(low)
+        }
+    }
+}
+
+/// A column of the metrics and detail tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum Column {
+    /// Metric name.
+    Name,
+
+    /// Latest value.
+    Value,
+
+    /// Average value over the last minute.
+    Avg,
+
+    /// Minimum value over the last minute.
+    Min,
+
+    /// Maximum value over the last minute.
+    Max,
+
+    /// Cumulative value of counters (e.g., the total reductions since the node started).
+    Raw,
+}
+
+impl Column {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Value => "Value",
+            Self::Avg => "Avg (1m)",
+            Self::Min => "Min (1m)",
+            Self::Max => "Max (1m)",
+            Self::Raw => "Raw",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NodeState {
+    header: Header,
+
+    // Metrics in the chart window.
+    history: VecDeque<Metrics>,
+
+    // Metrics retained for exports (`--retain`).
+    full_history: VecDeque<Metrics>,
+    retain: Duration,
+
+    // Offset in the metrics' timeline at which the 1m averages started to be accumulated
+    // (moved by the 'x' key).
+    window_start: Duration,
+
+    // Range of the replayed metrics in `full_history` (see `slide_window()`).
+    loaded: Option<RangeInclusive<Duration>>,
+}
+
+impl NodeState {
+    fn new(header: Header, retain: Duration) -> Self {
+        Self {
+            header,
+            history: VecDeque::new(),
+            full_history: VecDeque::new(),
+            retain,
+            window_start: Duration::ZERO,
+            loaded: None,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+        self.full_history.clear();
+        self.loaded = None;
+    }
+
+    /// Drops the collected metrics and starts the averaging window at the latest ones.
+    fn reset_window(&mut self) {
+        if let Some(metrics) = self.history.back() {
+            self.window_start = metrics.timestamp;
+        }
+        self.clear();
+    }
+
+    fn push_metrics(&mut self, metrics: Metrics) {
+        let timestamp = metrics.timestamp;
+        self.full_history.push_back(metrics.clone());
+        self.history.push_back(metrics);
+        while let Some(metrics) = self.history.pop_front() {
+            let duration = (timestamp - metrics.timestamp).as_secs();
+            if duration <= CHART_DURATION {
+                self.history.push_front(metrics);
+                break;
+            }
+            log::debug!("remove old metrics");
+        }
+        while let Some(metrics) = self.full_history.pop_front() {
+            if timestamp - metrics.timestamp <= self.retain {
+                self.full_history.push_front(metrics);
+                break;
+            }
+        }
+    }
+
+    /// Moves the replayed window so that it ends at `end`.
+    ///
+    /// Only the metrics that aren't loaded yet are read by `load(start, end)`, which returns the metrics
+    /// within `[start, end]`.
+    fn slide_window<F>(&mut self, end: Duration, mut load: F) -> anyhow::Result<()>
+    where
+        F: FnMut(Duration, Duration) -> anyhow::Result<Vec<Metrics>>,
+    {
+        let start = end.saturating_sub(self.retain);
+        match self.loaded.clone() {
+            Some(loaded) if start <= *loaded.end() && *loaded.start() <= end => {
+                self.full_history
+                    .retain(|metrics| (start..=end).contains(&metrics.timestamp));
+                if start < *loaded.start() {
+                    let older = load(start, *loaded.start())?;
+                    for metrics in older.into_iter().rev() {
+                        if metrics.timestamp < *loaded.start() {
+                            self.full_history.push_front(metrics);
+                        }
+                    }
                 }
+                if *loaded.end() < end {
+                    let newer = load(*loaded.end(), end)?;
+                    self.full_history.extend(
+                        newer
+                            .into_iter()
+                            .filter(|metrics| *loaded.end() < metrics.timestamp),
+                    );
+                }
+            }
+            _ => self.full_history = load(start, end)?.into(),
+        }
+        self.loaded = Some(start..=end);
+
+        let chart_duration = Duration::from_secs(CHART_DURATION);
+        let latest = self.full_history.back().map(|metrics| metrics.timestamp);
+        self.history = self
+            .full_history
+            .iter()
+            .filter(|metrics| {
+                latest.is_some_and(|latest| latest - metrics.timestamp <= chart_duration)
+            })
+            .cloned()
+            .collect();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Focus {
+    Main,
+    Sub,
+
+    /// A panel of `--dashboard` (only used as a key of the sticky column widths).
+    Panel(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        serde_json::from_value(serde_json::json!({
+            "system_version": "Erlang/OTP 26",
+            "node_name": "foo@localhost",
+            "start_time": "2024-01-01T09:00:00+09:00",
+        }))
+        .unwrap()
+    }
+
+    fn ui_state(replay_mode: bool) -> UiState {
+        UiState::new(
+            vec![header()],
+            replay_mode,
+            Vec::new(),
+            Theme::default(),
+            Duration::from_secs(CHART_DURATION),
+        )
+    }
+
+    fn push(ui: &mut UiState, secs: u64, items: &[(&str, MetricValue)]) {
+        let metrics = Metrics {
+            timestamp: Duration::from_secs(secs),
+            items: items
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        };
+        ui.nodes[ui.node_index].push_metrics(metrics);
+    }
+
+    fn counter(value: Option<f64>) -> MetricValue {
+        MetricValue::Counter {
+            raw_value: 0,
+            value,
+            parent: None,
+        }
+    }
+
+    #[test]
+    fn window_avg_works() {
+        let mut ui = ui_state(false);
+        assert!(ui.window_avg("foo").is_none());
+
+        push(&mut ui, 1, &[("gauge", MetricValue::signed_gauge(3))]);
+        assert!(matches!(
+            ui.window_avg("gauge"),
+            Some(MetricValue::Gauge { value: 3, .. })
+        ));
+        assert!(ui.window_avg("foo").is_none());
+
+        // The rate of a counter is unknown in the first sample, which is left out of the average.
+        push(
+            &mut ui,
+            2,
+            &[
+                ("gauge", MetricValue::signed_gauge(4)),
+                ("counter", counter(None)),
+            ],
+        );
+        push(
+            &mut ui,
+            3,
+            &[
+                ("gauge", MetricValue::signed_gauge(6)),
+                ("counter", counter(Some(10.0))),
+            ],
+        );
+        push(
+            &mut ui,
+            4,
+            &[
+                ("gauge", MetricValue::signed_gauge(-1)),
+                ("counter", counter(Some(20.0))),
+            ],
+        );
+        assert!(matches!(
+            ui.window_avg("gauge"),
+            Some(MetricValue::Gauge { value: 3, .. })
+        ));
+        assert!(matches!(
+            ui.window_avg("counter"),
+            Some(MetricValue::Counter { value: Some(v), .. }) if v == 15.0
+        ));
+
+        // A counter with no rate yet has no average.
+        let mut ui = ui_state(false);
+        push(&mut ui, 1, &[("counter", counter(None))]);
+        assert!(ui.window_avg("counter").is_none());
+    }
+
+    #[test]
+    fn selected_root_name_falls_back_to_last_row() {
+        let mut ui = ui_state(false);
+        assert_eq!(ui.selected_root_name(), None);
+
+        let gauge = MetricValue::signed_gauge(1);
+        push(
+            &mut ui,
+            1,
+            &[
+                ("a", gauge.clone()),
+                ("b", gauge.clone()),
+                ("c", gauge.clone()),
+            ],
+        );
+        assert_eq!(ui.selected_root_name(), Some("a"));
+        ui.metrics_table_state.select(Some(2));
+        assert_eq!(ui.selected_root_name(), Some("c"));
+
+        // "c" is no longer reported, so the selection is out of range.
+        push(&mut ui, 2, &[("a", gauge.clone()), ("b", gauge.clone())]);
+        assert_eq!(ui.selected_root_name(), Some("b"));
+        push(&mut ui, 3, &[("a", gauge)]);
+        assert_eq!(ui.selected_root_name(), Some("a"));
+        push(&mut ui, 4, &[]);
+        assert_eq!(ui.selected_root_name(), None);
+    }
+
+    #[test]
+    fn header_time_follows_shown_metrics() {
+        let start_time = header().start_time;
+
+        // In realtime mode, the offsets are measured from the start of the poller.
+        let mut ui = ui_state(false);
+        ui.sync_elapsed();
+        assert_eq!(ui.header_time(), start_time);
+        push(&mut ui, 1, &[]);
+        push(&mut ui, 3, &[]);
+        ui.sync_elapsed();
+        assert_eq!(ui.header_time(), start_time + Duration::from_secs(3));
+
+        // In replay mode, the offsets are the recorded ones, and each record file has its own start time.
+        let mut compared = header();
+        compared.start_time += chrono::Duration::hours(1);
+        let mut ui = UiState::new(
+            vec![header(), compared.clone()],
+            true,
+            Vec::new(),
+            Theme::default(),
+            Duration::from_secs(CHART_DURATION),
+        );
+        push(&mut ui, 120, &[]);
+        ui.node_index = 1;
+        push(&mut ui, 30, &[]);
+        ui.sync_elapsed();
+        assert_eq!(
+            ui.header_time(),
+            compared.start_time + Duration::from_secs(30)
+        );
+        ui.node_index = 0;
+        ui.sync_elapsed();
+        assert_eq!(ui.header_time(), start_time + Duration::from_secs(120));
+    }
+
+    #[test]
+    fn avg_availability_follows_metrics_timeline() {
+        let mut ui = ui_state(false);
+        push(&mut ui, 58, &[]);
+        ui.sync_elapsed();
+        assert!(!ui.is_avg_available());
+        push(&mut ui, 59, &[]);
+        ui.sync_elapsed();
+        assert!(ui.is_avg_available());
+
+        // The window restarts at the latest metrics, regardless of how much wall-clock time has passed.
+        ui.clear_history();
+        push(&mut ui, 60, &[]);
+        ui.sync_elapsed();
+        assert!(!ui.is_avg_available());
+        push(&mut ui, 118, &[]);
+        ui.sync_elapsed();
+        assert!(ui.is_avg_available());
+
+        // In replay mode, the window starts at the beginning of the recording.
+        let mut ui = ui_state(true);
+        push(&mut ui, 30, &[]);
+        ui.sync_elapsed();
+        assert!(!ui.is_avg_available());
+        push(&mut ui, 90, &[]);
+        ui.sync_elapsed();
+        assert!(ui.is_avg_available());
+    }
+
+    #[test]
+    fn slide_window_reads_only_new_metrics() {
+        let log = (0..300)
+            .map(|secs| Metrics {
+                timestamp: Duration::from_secs(secs),
+                items: BTreeMap::new(),
+            })
+            .collect::<Vec<_>>();
+        let mut reads = Vec::new();
+        let mut load = |start: Duration, end: Duration| {
+            reads.push((start.as_secs(), end.as_secs()));
+            Ok(log
+                .iter()
+                .filter(|metrics| (start..=end).contains(&metrics.timestamp))
+                .cloned()
+                .collect())
+        };
+        let timestamps = |history: &VecDeque<Metrics>| {
+            history
+                .iter()
+                .map(|metrics| metrics.timestamp.as_secs())
+                .collect::<Vec<_>>()
+        };
+
+        let mut node = NodeState::new(header(), Duration::from_secs(120));
+        node.slide_window(Duration::from_secs(150), &mut load)
+            .unwrap();
+        assert_eq!(
+            timestamps(&node.full_history),
+            (30..=150).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(&node.history), (90..=150).collect::<Vec<_>>());
+
+        node.slide_window(Duration::from_secs(151), &mut load)
+            .unwrap();
+        assert_eq!(
+            timestamps(&node.full_history),
+            (31..=151).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(&node.history), (91..=151).collect::<Vec<_>>());
+
+        node.slide_window(Duration::from_secs(149), &mut load)
+            .unwrap();
+        assert_eq!(
+            timestamps(&node.full_history),
+            (29..=149).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(&node.history), (89..=149).collect::<Vec<_>>());
+
+        // A jump outside of the loaded range reads the whole window again.
+        node.slide_window(Duration::from_secs(299), &mut load)
+            .unwrap();
+        assert_eq!(
+            timestamps(&node.full_history),
+            (179..=299).collect::<Vec<_>>()
+        );
+        assert_eq!(timestamps(&node.history), (239..=299).collect::<Vec<_>>());
+
+        assert_eq!(reads, [(30, 150), (150, 151), (29, 31), (179, 299)]);
+    }
+
+    fn render_lines(ui: &mut UiState, width: u16, height: u16) -> Vec<String> {
+        let backend = ratatui::backend::TestBackend::new(width, height);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| ui.render(f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| buffer.get(x, y).symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn metrics_table_fits_in_small_terminals() {
+        for layout_mode in [LayoutMode::Full, LayoutMode::Compact] {
+            let mut ui = ui_state(false);
+            ui.columns = vec![Column::Name, Column::Value, Column::Avg];
+            ui.layout_mode = layout_mode;
+            ui.recording_paused = Some(false);
+            ui.nodes
+                .push(NodeState::new(header(), Duration::from_secs(60)));
+            push(
+                &mut ui,
+                1,
+                &[
+                    ("foo", MetricValue::signed_gauge(1)),
+                    ("bar", MetricValue::signed_gauge(2)),
+                ],
+            );
+            ui.sync_elapsed();
+
+            let lines = render_lines(&mut ui, 80, 24);
+            assert!(lines.iter().any(|line| line.contains("bar")), "{lines:#?}");
+            assert!(lines.iter().any(|line| line.contains("foo")), "{lines:#?}");
+            assert!(lines.iter().any(|line| line.contains("Keys: 'k' key")));
+
+            // All the keys are listed over the dashboard.
+            ui.show_keys = true;
+            let lines = render_lines(&mut ui, 80, 24);
+            for key in ["Quit:", "Threshold/Base:", "Close:"] {
+                assert!(lines.iter().any(|line| line.contains(key)), "{lines:#?}");
             }
         }
     }
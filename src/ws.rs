@@ -0,0 +1,446 @@
+//! A minimal WebSocket server that broadcasts polled metrics (`--ws-listen`).
+//!
+//! Only what is needed to push text messages to browsers is implemented:
+//! the opening handshake, unmasked server-to-client text frames, and replies to the ping and close
+//! frames sent by clients. Other frames sent by clients are discarded.
+use crate::metrics::{Header, Metrics};
+use anyhow::Context;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A client that cannot receive a message within this time is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Number of messages queued per client. A client whose queue is full is disconnected
+// so that it does not delay the polling thread.
+const CLIENT_QUEUE_SIZE: usize = 16;
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+// See https://datatracker.ietf.org/doc/html/rfc6455#section-1.3
+const ACCEPT_KEY_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// See https://datatracker.ietf.org/doc/html/rfc6455#section-5.2
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug)]
+pub struct WsServer {
+    clients: Arc<Mutex<Vec<Client>>>,
+}
+
+/// A connected client, whose messages are written by its own thread.
+#[derive(Debug)]
+struct Client {
+    queue: SyncSender<Arc<[u8]>>,
+}
+
+impl WsServer {
+    /// Starts accepting WebSocket clients on `addr` in a background thread.
+    pub fn start(addr: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("failed to listen on {addr} for WebSocket clients"))?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("failed to accept a WebSocket client: {e}");
+                        continue;
+                    }
+                };
+                let clients = accepted.clone();
+                std::thread::spawn(move || {
+                    match handshake(&stream).and_then(|()| Client::start(stream)) {
+                        Ok(client) => {
+                            clients
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .push(client);
+                        }
+                        Err(e) => log::warn!("WebSocket handshake failed: {e}"),
+                    }
+                });
+            }
+        });
+        Ok(Self { clients })
+    }
+
+    /// Sends a metrics message to all the clients.
+    ///
+    /// The message is a JSON object like
+    /// `{"node": "foo@localhost", "timestamp": "2024-01-01T00:00:00.000Z", "metrics": {"memory.total_bytes": 1234, ...}}`.
+    /// Counters are given as the delta per second (`null` for the first poll), and the timestamp is
+    /// formatted according to `--timestamp-format`.
+    ///
+    /// This only queues the message, so a slow client never blocks the caller (i.e., the polling thread).
+    pub fn broadcast(&self, header: &Header, metrics: &Metrics) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.is_empty() {
+            return;
+        }
+        let message = serde_json::json!({
+            "node": header.node_name,
+            "timestamp": crate::export::timestamp_json(header.wall_clock(metrics.timestamp)),
+            "metrics": metrics
+                .items
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_f64().into()))
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        let frame = Arc::<[u8]>::from(frame(OPCODE_TEXT, message.to_string().as_bytes()));
+        clients.retain(|client| match client.queue.try_send(frame.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::warn!("WebSocket client is too slow to receive messages and is disconnected");
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+impl Client {
+    /// Starts the threads that write queued messages to the client and read the frames it sends.
+    fn start(stream: TcpStream) -> anyhow::Result<Self> {
+        let peer_addr = stream.peer_addr()?;
+        log::debug!("WebSocket client connected: {peer_addr}");
+        stream.set_read_timeout(None)?;
+        let reader = stream.try_clone()?;
+        let writer = Arc::new(Mutex::new(stream));
+
+        let (queue, rx) = mpsc::sync_channel::<Arc<[u8]>>(CLIENT_QUEUE_SIZE);
+        let stream = writer.clone();
+        std::thread::spawn(move || {
+            for frame in rx {
+                let result = stream
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .write_all(&frame);
+                if let Err(e) = result {
+                    log::debug!("WebSocket client {peer_addr} disconnected: {e}");
+                    break;
+                }
+            }
+            // The queue is closed when the client is removed by `broadcast()` (e.g., as it is too slow),
+            // and shutting down the connection also stops the reader thread.
+            let _ = stream
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .shutdown(Shutdown::Both);
+        });
+
+        // The replies to ping and close frames are written directly (instead of via the queue)
+        // so that they are not dropped when the queue is full.
+        std::thread::spawn(move || {
+            let result = serve_control_frames(&reader, |frame| {
+                writer
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .write_all(frame)
+            });
+            match result {
+                Ok(()) => log::debug!("WebSocket client {peer_addr} closed the connection"),
+                Err(e) => log::debug!("WebSocket client {peer_addr} disconnected: {e}"),
+            }
+            // This makes the pending and next writes fail, which stops the writer thread.
+            let _ = reader.shutdown(Shutdown::Both);
+        });
+        Ok(Self { queue })
+    }
+}
+
+/// Reads the frames sent by a client until it sends a close frame, replying to ping and close frames by `write`.
+fn serve_control_frames(
+    mut reader: impl Read,
+    mut write: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    loop {
+        let (opcode, payload) = read_frame(&mut reader)?;
+        match opcode {
+            OPCODE_PING => write(&frame(OPCODE_PONG, &payload))?,
+            OPCODE_CLOSE => {
+                // The status code of the client (if any) is echoed back.
+                write(&frame(OPCODE_CLOSE, payload.get(..2).unwrap_or_default()))?;
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads a frame sent by a client and returns its opcode and unmasked payload.
+///
+/// Fragmented messages are not reassembled since only control frames are handled.
+fn read_frame(mut reader: impl Read) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        n => u64::from(n),
+    };
+    if len > MAX_REQUEST_SIZE as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("too large WebSocket frame: {len} bytes"),
+        ));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+fn handshake(mut stream: &TcpStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        anyhow::ensure!(n > 0, "connection closed during the handshake");
+        request.extend_from_slice(&buf[..n]);
+        anyhow::ensure!(request.len() <= MAX_REQUEST_SIZE, "request is too large");
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let key = request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("sec-websocket-key")
+            .then(|| value.trim().to_owned())
+    });
+    let Some(key) = key else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n")?;
+        anyhow::bail!("not a WebSocket upgrade request");
+    };
+
+    let accept = accept_key(&key);
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    )?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    Ok(())
+}
+
+/// Returns the `Sec-WebSocket-Accept` value for the `Sec-WebSocket-Key` of a client.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{key}{ACCEPT_KEY_GUID}").as_bytes()))
+}
+
+/// Builds an unmasked frame with the FIN bit set.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => frame.push(n as u8),
+        n if n <= usize::from(u16::MAX) => {
+            frame.push(126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(char::from(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3F]));
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_works() {
+        // The example in https://datatracker.ietf.org/doc/html/rfc6455#section-1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn sha1_and_base64_work() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&sha1(b"")), "2jmj7l5rSw0yVb/vlWAYkK/YBwk=");
+        // Around the padding boundary of a block.
+        assert_eq!(base64(&sha1(&[b'a'; 55])), "wci73CJ5bijA4VFj0giZtlYh1lo=");
+        assert_eq!(base64(&sha1(&[b'a'; 56])), "wtszD2CDhUyZ1LW/tujynyAb5pk=");
+        assert_eq!(base64(&sha1(&[b'a'; 1000])), "KR6abGaZSUm1e6XmUDYemPw2sbo=");
+    }
+
+    #[test]
+    fn frames_work() {
+        // The examples in https://datatracker.ietf.org/doc/html/rfc6455#section-5.7
+        assert_eq!(
+            frame(OPCODE_TEXT, b"Hello"),
+            [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]
+        );
+        let masked = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        assert_eq!(
+            read_frame(&masked[..]).unwrap(),
+            (OPCODE_TEXT, b"Hello".to_vec())
+        );
+        let long = vec![b'x'; 256];
+        assert_eq!(read_frame(&frame(OPCODE_TEXT, &long)[..]).unwrap().1, long);
+        assert!(read_frame(&[0x81, 0x85, 0x37][..]).is_err());
+    }
+
+    #[test]
+    fn serve_control_frames_works() {
+        let mut input = frame(OPCODE_PING, b"hi");
+        input.extend(frame(OPCODE_TEXT, b"ignored"));
+        input.extend(frame(OPCODE_CLOSE, &1000u16.to_be_bytes()));
+        input.extend(frame(OPCODE_PING, b"after close"));
+        let mut output = Vec::new();
+        serve_control_frames(&input[..], |frame| {
+            output.extend_from_slice(frame);
+            Ok(())
+        })
+        .unwrap();
+        let mut expected = frame(OPCODE_PONG, b"hi");
+        expected.extend(frame(OPCODE_CLOSE, &1000u16.to_be_bytes()));
+        assert_eq!(output, expected);
+
+        // The connection is closed without a close frame.
+        let input = frame(OPCODE_PING, b"hi");
+        assert!(serve_control_frames(&input[..], |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn slow_clients_are_disconnected() {
+        let header: Header = serde_json::from_value(serde_json::json!({
+            "system_version": "Erlang/OTP 26",
+            "node_name": "foo@localhost",
+            "start_time": "2024-01-01T09:00:00+09:00",
+        }))
+        .unwrap();
+        let metrics = Metrics {
+            timestamp: Duration::ZERO,
+            items: Default::default(),
+        };
+
+        // Nothing is received from `slow`.
+        let (queue, slow) = mpsc::sync_channel(CLIENT_QUEUE_SIZE);
+        let (closed_queue, closed) = mpsc::sync_channel(CLIENT_QUEUE_SIZE);
+        drop(closed);
+        let server = WsServer {
+            clients: Arc::new(Mutex::new(vec![
+                Client { queue },
+                Client {
+                    queue: closed_queue,
+                },
+            ])),
+        };
+        server.broadcast(&header, &metrics);
+        assert_eq!(server.clients.lock().unwrap().len(), 1);
+        for _ in 1..CLIENT_QUEUE_SIZE {
+            server.broadcast(&header, &metrics);
+        }
+        assert_eq!(server.clients.lock().unwrap().len(), 1);
+        server.broadcast(&header, &metrics);
+        assert!(server.clients.lock().unwrap().is_empty());
+        assert_eq!(slow.try_iter().count(), CLIENT_QUEUE_SIZE);
+    }
+}